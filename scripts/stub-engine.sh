@@ -0,0 +1,18 @@
+#!/bin/sh
+# Stand-in UCCI engine for trying the external engine player without a real one:
+# it ignores the position and answers every go by moving black's right ma
+# back and forth between h9 and g7, set it up with
+#   pub const ENGINE_PATH: Option<&str> = Some("scripts/stub-engine.sh");
+moves=0
+while read -r command args; do
+    case "$command" in
+        ucci) echo "id name stub"; echo "ucciok" ;;
+        uci) echo "id name stub"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        go)
+            if [ $((moves % 2)) -eq 0 ]; then echo "bestmove h9g7"; else echo "bestmove g7h9"; fi
+            moves=$((moves + 1))
+            ;;
+        quit) echo "bye"; exit 0 ;;
+    esac
+done
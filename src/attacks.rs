@@ -0,0 +1,157 @@
+// Where each piece can step from each point, worked out once so that move
+// generation and check detection only look up squares instead of doing
+// bound checks. Points are numbered x + y * BW like `Board::points`.
+use crate::{
+    chess::{BH, BW},
+    pieces::PlayerColor,
+    pos::Pos,
+};
+use std::sync::OnceLock;
+
+pub(crate) const POINTS: usize = BW * BH;
+
+pub(crate) struct Tables {
+    // points along left, right, up and down, nearest first
+    pub rays: Vec<[Vec<usize>; 4]>,
+    // (target, leg) for every ma jump
+    pub ma: Vec<Vec<(usize, usize)>>,
+    // (ma point, leg) for every ma that could jump onto the point
+    pub ma_attackers: Vec<Vec<(usize, usize)>>,
+    // the tables below are indexed by `side(color)` first
+    pub jiang: [Vec<Vec<usize>>; 2],
+    pub shi: [Vec<Vec<usize>>; 2],
+    // (target, eye)
+    pub xiang: [Vec<Vec<(usize, usize)>>; 2],
+    pub bing: [Vec<Vec<usize>>; 2],
+    // points a bing of that color could take the point from
+    pub bing_attackers: [Vec<Vec<usize>>; 2],
+}
+
+pub(crate) fn side(color: PlayerColor) -> usize {
+    match color {
+        PlayerColor::Red => 0,
+        PlayerColor::Black => 1,
+    }
+}
+
+pub(crate) fn point(pos: Pos) -> usize {
+    pos.0 as usize + pos.1 as usize * BW
+}
+
+pub(crate) fn pos(point: usize) -> Pos {
+    Pos((point % BW) as i32, (point / BW) as i32)
+}
+
+fn on_board(pos: Pos) -> bool {
+    pos.in_bound(Pos(0, 0), Pos(BW as i32 - 1, BH as i32 - 1))
+}
+
+fn in_palace(pos: Pos, color: PlayerColor) -> bool {
+    match color {
+        PlayerColor::Red => pos.in_bound(Pos(3, 0), Pos(5, 2)),
+        PlayerColor::Black => pos.in_bound(Pos(3, 7), Pos(5, 9)),
+    }
+}
+
+// before the river
+fn own_half(pos: Pos, color: PlayerColor) -> bool {
+    match color {
+        PlayerColor::Red => pos.1 <= 4,
+        PlayerColor::Black => pos.1 >= 5,
+    }
+}
+
+const ORTHOGONAL: [Pos; 4] = [Pos(-1, 0), Pos(1, 0), Pos(0, 1), Pos(0, -1)];
+// indices into `Tables::rays`
+pub(crate) const UP: usize = 2;
+pub(crate) const DOWN: usize = 3;
+const DIAGONAL: [Pos; 4] = [Pos(-1, -1), Pos(1, 1), Pos(-1, 1), Pos(1, -1)];
+const MA_JUMPS: [Pos; 8] = [
+    Pos(-1, -2),
+    Pos(1, -2),
+    Pos(-2, -1),
+    Pos(2, -1),
+    Pos(-1, 2),
+    Pos(1, 2),
+    Pos(-2, 1),
+    Pos(2, 1),
+];
+
+fn ma_leg(jump: Pos) -> Pos {
+    if jump.0 == 2 || jump.0 == -2 {
+        Pos(jump.0 / 2, 0)
+    } else {
+        Pos(0, jump.1 / 2)
+    }
+}
+
+fn build() -> Tables {
+    let mut tables = Tables {
+        rays: vec![],
+        ma: vec![],
+        ma_attackers: vec![vec![]; POINTS],
+        jiang: [vec![], vec![]],
+        shi: [vec![], vec![]],
+        xiang: [vec![], vec![]],
+        bing: [vec![], vec![]],
+        bing_attackers: [vec![vec![]; POINTS], vec![vec![]; POINTS]],
+    };
+    for from in 0..POINTS {
+        let at = pos(from);
+        tables.rays.push(ORTHOGONAL.map(|dir| {
+            let mut ray = vec![];
+            let mut to = at + dir;
+            while on_board(to) {
+                ray.push(point(to));
+                to = to + dir;
+            }
+            ray
+        }));
+        let mut jumps = vec![];
+        for jump in MA_JUMPS {
+            let to = at + jump;
+            if on_board(to) {
+                let leg = point(at + ma_leg(jump));
+                jumps.push((point(to), leg));
+                tables.ma_attackers[point(to)].push((from, leg));
+            }
+        }
+        tables.ma.push(jumps);
+        for color in [PlayerColor::Red, PlayerColor::Black] {
+            let s = side(color);
+            let steps = |dirs: [Pos; 4]| -> Vec<usize> {
+                dirs.iter()
+                    .map(|dir| at + *dir)
+                    .filter(|to| in_palace(*to, color))
+                    .map(point)
+                    .collect()
+            };
+            tables.jiang[s].push(steps(ORTHOGONAL));
+            tables.shi[s].push(steps(DIAGONAL));
+            tables.xiang[s].push(
+                DIAGONAL
+                    .iter()
+                    .map(|dir| (at + *dir * 2, at + *dir))
+                    .filter(|(to, _)| on_board(*to) && own_half(*to, color))
+                    .map(|(to, eye)| (point(to), point(eye)))
+                    .collect(),
+            );
+            let forward = if color == PlayerColor::Red { Pos(0, 1) } else { Pos(0, -1) };
+            let mut dirs = vec![forward];
+            if !own_half(at, color) {
+                dirs.extend([Pos(-1, 0), Pos(1, 0)]);
+            }
+            let bing: Vec<usize> = dirs.into_iter().map(|dir| at + dir).filter(|to| on_board(*to)).map(point).collect();
+            for to in bing.iter() {
+                tables.bing_attackers[s][*to].push(from);
+            }
+            tables.bing[s].push(bing);
+        }
+    }
+    tables
+}
+
+pub(crate) fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(build)
+}
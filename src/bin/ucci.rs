@@ -0,0 +1,151 @@
+// UCCI engine over stdin/stdout, for xiangqi GUIs, tournament managers and scripts:
+//   ucci / isready / position {startpos | fen <fen>} [moves <iccs>...]
+//   go [depth <plies> | time <ms> [movestogo <n>] [increment <ms>] | movetime <ms> | infinite]
+//   stop / quit
+use chinese_chess::{
+    chess::{ChessGame, START_FEN},
+    engine::{self, Limits},
+    notation,
+    pieces::PlayerColor,
+};
+use std::{
+    io::{self, BufRead, Write},
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// depth used when go gives no limit at all
+const DEFAULT_DEPTH: u32 = 4;
+// depth ceiling for timed and infinite searches
+const MAX_DEPTH: u32 = 64;
+// moves a clock without movestogo is expected to last for
+const MOVES_TO_GO: u32 = 30;
+
+fn send(line: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+// position [startpos | fen <fen>] [moves <move>...]
+fn parse_position(args: &[&str]) -> Option<ChessGame> {
+    let moves_at = args.iter().position(|arg| *arg == "moves").unwrap_or(args.len());
+    let fen = match args.first() {
+        Some(&"startpos") => START_FEN.to_string(),
+        Some(&"fen") => args[1..moves_at].join(" "),
+        _ => return None,
+    };
+    let mut game = ChessGame::from_fen(&fen).ok()?;
+    for iccs in args.iter().skip(moves_at + 1) {
+        let (from, action) = notation::from_iccs(&game.board, iccs)?;
+        game.playable_move(from, action.target())?;
+        game.play(from, action);
+    }
+    Some(game)
+}
+
+fn parse_limits(args: &[&str]) -> Limits {
+    let value = |name: &str| -> Option<u64> {
+        let i = args.iter().position(|arg| *arg == name)?;
+        args.get(i + 1)?.parse().ok()
+    };
+    let mut limits = Limits { depth: MAX_DEPTH, ..Default::default() };
+    if let Some(depth) = value("depth") {
+        limits.depth = depth as u32;
+    } else if let Some(movetime) = value("movetime") {
+        limits.deadline = Some(Instant::now() + Duration::from_millis(movetime));
+    } else if let Some(time) = value("time") {
+        let moves_to_go = value("movestogo").unwrap_or(MOVES_TO_GO as u64).max(1);
+        let budget = time / moves_to_go + value("increment").unwrap_or(0);
+        // keep a margin for the reply to reach the GUI
+        let budget = budget.min(time.saturating_sub(50));
+        limits.deadline = Some(Instant::now() + Duration::from_millis(budget));
+    } else if !args.contains(&"infinite") {
+        limits.depth = DEFAULT_DEPTH;
+    }
+    limits
+}
+
+struct Search {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    // ends by itself, without a stop command
+    bounded: bool,
+}
+
+impl Search {
+    fn start(game: &ChessGame, mut limits: Limits) -> Search {
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(stop.clone());
+        let bounded = limits.depth < MAX_DEPTH || limits.deadline.is_some();
+        let board = game.board.clone();
+        let color = game.player.unwrap_or(PlayerColor::Red);
+        let handle = thread::spawn(move || {
+            let best = engine::search(&board, color, &limits, |depth, score, (from, action)| {
+                send(&format!("info depth {} score {} pv {}", depth, score, notation::to_iccs(from, action)));
+            });
+            match best {
+                Some((from, action)) => send(&format!("bestmove {}", notation::to_iccs(from, action))),
+                None => send("nobestmove"),
+            }
+        });
+        Search { stop, handle, bounded }
+    }
+
+    // ends the search, which still reports its best move
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+fn main() {
+    let mut game = ChessGame::new();
+    let mut search: Option<Search> = None;
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = args.split_first() else { continue };
+        match *command {
+            "ucci" => {
+                send("id name chinese-chess");
+                send("id author Sugar-Coder");
+                send("ucciok");
+            }
+            "isready" => send("readyok"),
+            "position" => match parse_position(args) {
+                Some(position) => game = position,
+                None => send(&format!("info string bad position: {}", args.join(" "))),
+            },
+            "go" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+                search = Some(Search::start(&game, parse_limits(args)));
+            }
+            "stop" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+            }
+            "quit" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+                send("bye");
+                return;
+            }
+            // setoption, banmoves, probe... are not supported
+            _ => {}
+        }
+    }
+    // input ended, let a bounded search finish so scripts get their move
+    if let Some(running) = search.take() {
+        if running.bounded {
+            let _ = running.handle.join();
+        } else {
+            running.stop();
+        }
+    }
+}
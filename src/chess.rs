@@ -1,34 +1,54 @@
-use crate::{configs::{BH, BW, GL, BCX, BCY, MAX_REGRET}, pos::Pos};
+use crate::attacks::{self, point, side, tables, DOWN, POINTS, UP};
+use crate::pos::Pos;
+use crate::repetition::Ruleset;
+use crate::zobrist::{piece_key, SIDE_KEY};
 use crate::pieces::{Action, Piece, PlayerColor};
-use bevy::prelude::*;
+use std::fmt;
 
+// board grid point count
+pub const BW: usize = 9; // board width, horizontal axis
+pub const BH: usize = 10; // board height, vertical axis
+// opening position
+pub const START_FEN: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+// moves per side without a capture or a bing going forward before the game is drawn
+pub const DEFAULT_MOVE_LIMIT: u32 = 60;
 
-type Grid = Option<(PlayerColor, Piece)>;
+pub type Grid = Option<(PlayerColor, Piece)>;
 
 #[derive(Clone)]
 pub struct Board {
-    pub points: Vec<Grid>,
-    pub center: Vec2, // world coordinate of the center of the board
+    points: [Grid; POINTS],
+    // where each side's jiang stands, kept up to date by `set`, `make` and `unmake`
+    jiang: [Option<usize>; 2],
+    // zobrist hash, kept up to date the same way
+    hash: u64,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
     pub fn new() -> Board {
         Board { 
-            points: vec![None; BW * BH],
-            center: Vec2::new(BCX, BCY),
+            points: [None; POINTS],
+            jiang: [None; 2],
+            hash: 0,
         }
     }
 
     pub fn i(&self, pos: Pos) -> usize {
-        return pos.0 as usize + pos.1 as usize * BW;
+        point(pos)
     }
 
     pub fn pos(&self, i: usize) -> Pos {
-        return Pos{0: (i % BW) as i32, 1: (i / BW) as i32};
+        attacks::pos(i)
     }
 
     fn in_board(&self, pos: Pos) -> bool {
-        return pos.0 <= 8 && pos.0 >= 0 && pos.1 >= 0 && pos.1 <= 9;
+        pos.0 <= 8 && pos.0 >= 0 && pos.1 >= 0 && pos.1 <= 9
     }
 
     pub fn get(&self, pos: Pos) -> Option<&Grid> {
@@ -36,50 +56,284 @@ impl Board {
         if !self.in_board(pos) {
             return None;
         }
-        return Some(&self.points[self.i(pos)]);
+        Some(&self.points[self.i(pos)])
+    }
+
+    pub(crate) fn at(&self, point: usize) -> Grid {
+        self.points[point]
     }
 
     pub fn set(&mut self, pos: Pos, grid: Grid) {
         if self.in_board(pos) {
             let idx = self.i(pos);
-            self.points[idx] = grid;
+            self.put(idx, grid);
+        }
+    }
+
+    fn put(&mut self, point: usize, grid: Grid) {
+        if let Some((c, p)) = self.points[point] {
+            self.hash ^= piece_key(c, p, point);
+            if p == Piece::Jiang && self.jiang[side(c)] == Some(point) {
+                self.jiang[side(c)] = None;
+            }
+        }
+        if let Some((c, p)) = grid {
+            self.hash ^= piece_key(c, p, point);
+            if p == Piece::Jiang {
+                self.jiang[side(c)] = Some(point);
+            }
+        }
+        self.points[point] = grid;
+    }
+
+    // identifies the placement and the side to move, which flips with every `make` and `unmake`;
+    // a board starts with red to move unless its FEN says otherwise
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub(crate) fn flip_side(&mut self) {
+        self.hash ^= SIDE_KEY;
+    }
+
+    // every piece on the board with the point it stands on
+    pub fn pieces(&self) -> impl Iterator<Item = (Pos, PlayerColor, Piece)> + '_ {
+        self.points
+            .iter()
+            .enumerate()
+            .filter_map(|(i, grid)| grid.map(|(c, p)| (attacks::pos(i), c, p)))
+    }
+
+    pub fn find_jiang(&self, color: PlayerColor) -> Option<Pos> {
+        self.jiang[side(color)].map(attacks::pos)
+    }
+
+    // whether any piece of color `by` can take the piece standing on `pos`,
+    // found by looking outwards from `pos` the way each kind of piece moves
+    pub fn is_attacked(&self, pos: Pos, by: PlayerColor) -> bool {
+        let target = point(pos);
+        let tables = tables();
+        let s = side(by);
+        let facing_jiang = matches!(self.at(target), Some((c, Piece::Jiang)) if c != by);
+        for (dir, ray) in tables.rays[target].iter().enumerate() {
+            let mut screens = 0;
+            for i in ray {
+                let Some((c, p)) = self.at(*i) else { continue };
+                if c == by {
+                    let first = screens == 0;
+                    let vertical = dir == UP || dir == DOWN;
+                    if (first && p == Piece::Che)
+                        || (first && vertical && facing_jiang && p == Piece::Jiang)
+                        || (screens == 1 && p == Piece::Pao)
+                    {
+                        return true;
+                    }
+                }
+                screens += 1;
+                if screens > 1 {
+                    break;
+                }
+            }
+        }
+        for (from, leg) in tables.ma_attackers[target].iter() {
+            if self.at(*from) == Some((by, Piece::Ma)) && self.at(*leg).is_none() {
+                return true;
+            }
+        }
+        if tables.bing_attackers[s][target].iter().any(|from| self.at(*from) == Some((by, Piece::Bing))) {
+            return true;
+        }
+        // the palace pieces and xiang move the same way back, as long as `pos` is on their ground
+        let back = |steps: &[usize], piece: Piece| {
+            steps.iter().any(|from| self.at(*from) == Some((by, piece)))
+        };
+        let jiang = &tables.jiang[s];
+        if back(&jiang[target], Piece::Jiang) && jiang[target].iter().any(|from| jiang[*from].contains(&target)) {
+            return true;
+        }
+        let shi = &tables.shi[s];
+        if back(&shi[target], Piece::Shi) && shi[target].iter().any(|from| shi[*from].contains(&target)) {
+            return true;
+        }
+        tables.xiang[s][target].iter().any(|(from, eye)| {
+            self.at(*from) == Some((by, Piece::Xiang))
+                && self.at(*eye).is_none()
+                && tables.xiang[s][*from].iter().any(|(to, _)| *to == target)
+        })
+    }
+
+    pub fn in_check(&self, color: PlayerColor) -> bool {
+        match self.find_jiang(color) {
+            Some(pos) => self.is_attacked(pos, color.next()),
+            None => false,
+        }
+    }
+
+    // every legal move of the given color
+    pub fn legal_moves(&self, color: PlayerColor) -> Vec<(Pos, Action)> {
+        let mut moves = vec![];
+        let mut actions = vec![];
+        let mut board = self.clone();
+        for (from, c, p) in self.pieces() {
+            if c != color {
+                continue;
+            }
+            actions.clear();
+            p.push_moves(self, from, color, &mut actions);
+            for action in actions.iter() {
+                if board.is_safe(color, from, *action) {
+                    moves.push((from, *action));
+                }
+            }
         }
+        moves
     }
 
+    // drop the moves that leave the mover's jiang under attack
     pub fn filter_save_moves(&self, color: PlayerColor, from: Pos, actions: Vec<Action>) -> Vec<Action> {
-        info!(
-            "available actions count={}",
-            actions.len()
-        );
+        let mut board = self.clone();
         actions
+            .into_iter()
+            .filter(|action| board.is_safe(color, from, *action))
+            .collect()
+    }
+
+    // whether the move keeps the mover's jiang out of check, tried in place
+    fn is_safe(&mut self, color: PlayerColor, from: Pos, action: Action) -> bool {
+        let captured = self.make(from, action);
+        let safe = !self.in_check(color);
+        self.unmake(from, action, captured);
+        safe
     }
 
     pub fn play(&self, from: Pos, action: Action) -> Self {
         let mut res = self.clone();
-        if let Some((c, p)) = self.get(from).unwrap() {
-            match action {
-                Action::Go(to) => {
-                    res.set(from, None);
-                    info!("setting Pos{} to {}", to, *p);
-                    res.set(to, Some((*c, *p)));
-                },
-                Action::Take(to) => {
-                    info!("{} taking Pos{}", *p, to);
-                    res.set(from, None);
-                    res.set(to, Some((*c, *p)));
-                }
+        res.make(from, action);
+        res
+    }
+
+    // play a move in place, returns what stood on the target point
+    pub fn make(&mut self, from: Pos, action: Action) -> Grid {
+        let (from, to) = (point(from), point(action.target()));
+        let captured = self.points[to];
+        let moving = self.points[from];
+        if moving.is_some() {
+            self.put(from, None);
+            self.put(to, moving);
+            self.flip_side();
+        }
+        captured
+    }
+
+    // take back a move done by `make`
+    pub fn unmake(&mut self, from: Pos, action: Action, captured: Grid) {
+        let (from, to) = (point(from), point(action.target()));
+        let moved = self.points[to];
+        if moved.is_some() {
+            self.flip_side();
+        }
+        self.put(from, moved);
+        self.put(to, captured);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WinReason {
+    Checkmate,
+    // in xiangqi the side left without a legal move loses
+    Stalemate,
+    GeneralCaptured,
+    // the loser kept repeating the position with forbidden moves, see `Ruleset`
+    PerpetualCheck,
+    PerpetualChase,
+    PerpetualMateThreat,
+    Resignation,
+    // the loser's clock ran out
+    Timeout,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    Repetition,
+    // `ChessGame::move_limit` moves by each side without a capture or a bing going forward
+    MoveLimit,
+    // nothing left that could give mate: no che, ma, pao or bing on either side
+    InsufficientMaterial,
+    // a draw offer was accepted
+    Agreement,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Win(PlayerColor, WinReason),
+    Draw(DrawReason),
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<PlayerColor> {
+        match self {
+            Outcome::Win(color, _) => Some(*color),
+            Outcome::Draw(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Win(color, WinReason::Checkmate) => write!(f, "{} wins by checkmate", color),
+            Outcome::Win(color, WinReason::Stalemate) => write!(f, "{} wins by stalemate", color),
+            Outcome::Win(color, WinReason::GeneralCaptured) => write!(f, "{} wins by capturing the jiang", color),
+            Outcome::Win(color, WinReason::PerpetualCheck) => write!(f, "{} wins, {} checked perpetually", color, color.next()),
+            Outcome::Win(color, WinReason::PerpetualChase) => write!(f, "{} wins, {} chased perpetually", color, color.next()),
+            Outcome::Win(color, WinReason::PerpetualMateThreat) => {
+                write!(f, "{} wins, {} threatened mate perpetually", color, color.next())
             }
+            Outcome::Win(color, WinReason::Timeout) => write!(f, "{} ran out of time, {} wins", color.next(), color),
+            Outcome::Win(color, WinReason::Resignation) => write!(f, "{} resigns, {} wins", color.next(), color),
+            Outcome::Draw(DrawReason::Repetition) => write!(f, "draw by repetition"),
+            Outcome::Draw(DrawReason::MoveLimit) => write!(f, "draw by the move limit"),
+            Outcome::Draw(DrawReason::InsufficientMaterial) => write!(f, "draw by insufficient material"),
+            Outcome::Draw(DrawReason::Agreement) => write!(f, "draw by agreement"),
         }
-        res
     }
 }
 
-#[derive(Resource)]
+// one move of the game record
+#[derive(Clone, Copy)]
+pub struct Move {
+    pub from: Pos,
+    pub action: Action,
+    pub captured: Grid,
+    // `ChessGame::quiet_plies` before the move
+    pub quiet_plies: u32,
+}
+
 pub struct ChessGame {
     pub board: Board,
     pub turn: u32,
+    // plies since the last capture or bing going forward
+    pub quiet_plies: u32,
     pub player: Option<PlayerColor>,
-    memo: Vec<Board>,
+    // every move played since `start_fen`
+    pub history: Vec<Move>,
+    // regretted moves, the last one is redone first
+    undone: Vec<Move>,
+    pub start_fen: String,
+    // how repeated positions are judged
+    pub ruleset: Ruleset,
+    // moves per side without a capture or a bing going forward that draw the game, None for no limit
+    pub move_limit: Option<u32>,
+    // decided by the players rather than on the board: resigned or drawn by agreement
+    pub result: Option<Outcome>,
+    // the side offering a draw, until the other side answers or moves
+    pub draw_offer: Option<PlayerColor>,
+}
+
+impl Default for ChessGame {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChessGame {
@@ -87,38 +341,18 @@ impl ChessGame {
         ChessGame {
             board: make_board(),
             turn: 0,
+            quiet_plies: 0,
             player: None,
-            memo: vec![],
-        }
-    }
-
-    pub fn in_bound(&self, world_position: &Vec2) -> bool {
-        let on_board_position = *world_position - self.board.center;
-        if on_board_position.x >= -4.5 * GL && on_board_position.x <= 4.5 * GL &&
-        on_board_position.y >= -5. * GL && on_board_position.y <= 5. * GL {
-                return true;
-        } else {
-            return false;
+            history: vec![],
+            undone: vec![],
+            start_fen: START_FEN.to_string(),
+            ruleset: Ruleset::default(),
+            move_limit: Some(DEFAULT_MOVE_LIMIT),
+            result: None,
+            draw_offer: None,
         }
     }
 
-    pub fn world_to_board(&self, world_position: &Vec2) -> Pos {
-        // world position must on board
-        let on_board_position = *world_position - self.board.center;
-        return Pos {
-            0: ((on_board_position.x + 4.5 * GL) / GL) as i32,
-            1: ((on_board_position.y + 5.0 * GL) / GL) as i32,
-        }
-    }
-
-    pub fn board_to_world(&self, pos: Pos) -> Transform {
-        Transform::from_xyz(
-            (pos.0 as f32 - 4.0) * GL + self.board.center.x, 
-            (pos.1 as f32 - 4.5) * GL + self.board.center.y, 
-            1.0
-        )
-    }
-
     // for movement judgement
     pub fn save_moves(&self, piece: Piece, from: Pos, color: PlayerColor) -> Vec<Action> {
         self.board.filter_save_moves(
@@ -133,10 +367,8 @@ impl ChessGame {
             // if self.turn == 0 { // who first attemp to move
             //     self.player = Some(*color);
             // }
-            if self.turn == 0 || self.player == Some(*color) {
+            if self.result.is_none() && (self.player.is_none() || self.player == Some(*color)) {
                 return Some(self.save_moves(*piece, from, *color));
-            } else {
-                info!("Not your turn");
             }
         }
         None
@@ -163,57 +395,302 @@ impl ChessGame {
     }
 
     pub fn play(&mut self, from: Pos, action: Action) {
-        if let Some((c, _)) = self.board.get(from).unwrap() {
+        if self.make(from, action) {
+            // a new move branches off, the regretted ones are gone
+            self.undone.clear();
+        }
+    }
+
+    fn make(&mut self, from: Pos, action: Action) -> bool {
+        if let Some((c, p)) = *self.board.get(from).unwrap() {
             if let Some(color) = self.player {
                 self.player = Some(color.next());
             } else {
+                // either side may open a new game, the one that does had the move at the start
+                if c == PlayerColor::Black {
+                    self.board.flip_side();
+                    self.turn += 1;
+                }
+                self.player = Some(c);
+                self.start_fen = self.to_fen();
                 self.player = Some(c.next());
             }
             self.turn += 1;
-            self.memo.push(self.board.clone());
-            if self.memo.len() > MAX_REGRET {
-                self.memo.remove(0);
+            // moving instead of answering declines the other side's offer
+            if self.draw_offer != Some(c) {
+                self.draw_offer = None;
+            }
+            let captured = self.board.make(from, action);
+            self.history.push(Move { from, action, captured, quiet_plies: self.quiet_plies });
+            let advance = p == Piece::Bing && action.target().0 == from.0;
+            self.quiet_plies = if captured.is_some() || advance { 0 } else { self.quiet_plies + 1 };
+            return true;
+        }
+        false
+    }
+
+    // None while the game is still going on
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.result.is_some() {
+            return self.result;
+        }
+        let color = self.player?;
+        for c in [color, color.next()] {
+            if self.board.find_jiang(c).is_none() {
+                return Some(Outcome::Win(c.next(), WinReason::GeneralCaptured));
             }
-            self.board = self.board.play(from, action);
         }
+        if !self.board.legal_moves(color).is_empty() {
+            return self.repetition().or_else(|| self.draw());
+        }
+        if self.board.in_check(color) {
+            Some(Outcome::Win(color.next(), WinReason::Checkmate))
+        } else {
+            Some(Outcome::Win(color.next(), WinReason::Stalemate))
+        }
+    }
+
+    fn draw(&self) -> Option<Outcome> {
+        let attacking = |(_, _, piece): &(Pos, PlayerColor, Piece)| {
+            matches!(piece, Piece::Che | Piece::Ma | Piece::Pao | Piece::Bing)
+        };
+        if !self.board.pieces().any(|piece| attacking(&piece)) {
+            return Some(Outcome::Draw(DrawReason::InsufficientMaterial));
+        }
+        match self.move_limit {
+            Some(limit) if self.quiet_plies >= limit * 2 => Some(Outcome::Draw(DrawReason::MoveLimit)),
+            _ => None,
+        }
+    }
+
+    pub fn resign(&mut self, color: PlayerColor) {
+        if self.result.is_none() {
+            self.result = Some(Outcome::Win(color.next(), WinReason::Resignation));
+            self.draw_offer = None;
+        }
+    }
+
+    pub fn time_out(&mut self, color: PlayerColor) {
+        if self.result.is_none() {
+            self.result = Some(Outcome::Win(color.next(), WinReason::Timeout));
+            self.draw_offer = None;
+        }
+    }
+
+    pub fn offer_draw(&mut self, color: PlayerColor) {
+        if self.result.is_none() {
+            self.draw_offer = Some(color);
+        }
+    }
+
+    // whether the other side had offered a draw for `color` to accept
+    pub fn accept_draw(&mut self, color: PlayerColor) -> bool {
+        if self.draw_offer != Some(color.next()) || self.result.is_some() {
+            return false;
+        }
+        self.draw_offer = None;
+        self.result = Some(Outcome::Draw(DrawReason::Agreement));
+        true
+    }
+
+    pub fn decline_draw(&mut self) {
+        self.draw_offer = None;
     }
 
     pub fn restart(&mut self) {
         self.board = make_board();
         self.player = None;
         self.turn = 0;
-        self.memo = vec![];
+        self.quiet_plies = 0;
+        self.result = None;
+        self.draw_offer = None;
+        self.history = vec![];
+        self.undone = vec![];
+        self.start_fen = START_FEN.to_string();
     }
 
     pub fn regret(&mut self) -> bool {
-        if self.memo.len() > 0 {
-            self.board = self.memo.pop().unwrap();
+        if let Some(m) = self.history.pop() {
+            self.board.unmake(m.from, m.action, m.captured);
             self.turn -= 1;
+            self.quiet_plies = m.quiet_plies;
+            // taking back a move takes the game back from a resignation or a draw too
+            self.result = None;
+            self.draw_offer = None;
             self.player = Some(self.player.unwrap().next());
+            self.undone.push(m);
             return true;
         }
-        return false;
+        false
+    }
+
+    // the board after the first `ply` moves of `history`, for looking back at the game
+    pub fn board_at(&self, ply: usize) -> Board {
+        let mut board = self.board.clone();
+        for m in self.history[ply.min(self.history.len())..].iter().rev() {
+            board.unmake(m.from, m.action, m.captured);
+        }
+        board
+    }
+
+    // nothing is played on after a resignation, an agreed draw or a loss on time
+    pub fn redo(&mut self) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        if let Some(m) = self.undone.pop() {
+            return self.make(m.from, m.action);
+        }
+        false
     }
 }
 
 fn make_board() -> Board {
-    let mut board = Board::new();
-    let pieces = vec![Piece::Che, Piece::Ma, Piece::Xiang, Piece::Shi, Piece::Jiang, Piece::Shi, Piece::Xiang, Piece::Ma, Piece::Che];
-    for i in 0..=8 {
-        board.points[i] = Some((PlayerColor::Red, pieces[i]));
-        board.points[i + 9 * BW] = Some((PlayerColor::Black, pieces[i]));
-    }
-    for i in vec![1, 7] {
-        let mut idx = board.i(Pos{0: i, 1: 7});
-        board.points[idx] = Some((PlayerColor::Black, Piece::Pao));
-        idx = board.i(Pos{0: i, 1: 2});
-        board.points[idx] = Some((PlayerColor::Red, Piece::Pao));
-    }
-    for i in vec![0, 2, 4, 6, 8] {
-        let mut idx = board.i(Pos{0: i, 1: 6});
-        board.points[idx] = Some((PlayerColor::Black, Piece::Bing));
-        idx = board.i(Pos{0: i, 1: 3});
-        board.points[idx] = Some((PlayerColor::Red, Piece::Bing));
-    }
-    board
-}
\ No newline at end of file
+    Board::from_fen(START_FEN).unwrap()
+}
+
+// count the leaf nodes of the legal move tree, for checking the move generators
+pub fn perft(board: &Board, color: PlayerColor, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves(color);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    let mut board = board.clone();
+    for (from, action) in moves {
+        let captured = board.make(from, action);
+        nodes += perft(&board, color.next(), depth - 1);
+        board.unmake(from, action, captured);
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // is_attacked looks from the target outwards, it has to agree with the generators
+    #[test]
+    fn is_attacked_agrees_with_takes() {
+        let fens = [
+            START_FEN,
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w",
+            "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w",
+            "4k4/9/9/9/9/9/9/9/9/4K4 w",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            for (target, color, _) in board.pieces() {
+                let by = color.next();
+                let takes = board.pieces().filter(|(_, c, _)| *c == by).any(|(from, _, p)| {
+                    p.moves(&board, from, by).iter().any(|a| matches!(a, Action::Take(to) if *to == target))
+                });
+                assert_eq!(board.is_attacked(target, by), takes, "{} {}", fen, target);
+            }
+        }
+    }
+
+    #[test]
+    fn hash_follows_make_and_unmake() {
+        let mut game = ChessGame::new();
+        let start = game.board.hash();
+        let moves = game.board.legal_moves(PlayerColor::Red);
+        for (from, action) in moves {
+            let captured = game.board.make(from, action);
+            assert_ne!(game.board.hash(), start);
+            game.board.unmake(from, action, captured);
+            assert_eq!(game.board.hash(), start);
+        }
+        let play = |game: &mut ChessGame, moves: &[&str]| {
+            for iccs in moves {
+                let (from, action) = crate::notation::from_iccs(&game.board, iccs).unwrap();
+                game.play(from, action);
+            }
+        };
+        // paos taking each other, compared with a board read from scratch
+        play(&mut game, &["h2e2", "h7e7", "e2e6", "e7e3"]);
+        let fen = game.to_fen();
+        assert_eq!(game.board.hash(), Board::from_fen(&fen).unwrap().hash());
+        // the same position reached in another order
+        let (mut a, mut b) = (ChessGame::new(), ChessGame::new());
+        play(&mut a, &["h2e2", "h9g7", "b0c2"]);
+        play(&mut b, &["b0c2", "h9g7", "h2e2"]);
+        assert_eq!(a.board.hash(), b.board.hash());
+        // same placement, other side to move
+        let placement = fen.split_whitespace().next().unwrap();
+        assert_ne!(Board::from_fen(placement).unwrap().hash(), Board::from_fen(&format!("{} b", placement)).unwrap().hash());
+    }
+
+    #[test]
+    fn board_at_goes_back_through_the_history() {
+        let mut game = ChessGame::new();
+        let mut fens = vec![game.board.to_fen()];
+        for iccs in ["h2e2", "h7e7", "e2e6", "e7e3"] {
+            let (from, action) = crate::notation::from_iccs(&game.board, iccs).unwrap();
+            game.play(from, action);
+            fens.push(game.board.to_fen());
+        }
+        for (ply, fen) in fens.iter().enumerate() {
+            assert_eq!(&game.board_at(ply).to_fen(), fen);
+        }
+        // the game itself is left alone
+        assert_eq!(game.board.to_fen(), fens[4]);
+        assert_eq!(game.history.len(), 4);
+    }
+
+    fn play_iccs(game: &mut ChessGame, moves: &[&str]) {
+        for iccs in moves {
+            let (from, action) = crate::notation::from_iccs(&game.board, iccs).unwrap();
+            game.play(from, action);
+        }
+    }
+
+    #[test]
+    fn regret_and_redo_the_whole_game() {
+        let mut game = ChessGame::new();
+        let start = (game.to_fen(), game.board.hash());
+        play_iccs(&mut game, &["h2e2", "h7e7", "e2e6", "e7e3", "h0g2", "h9g7"]);
+        let end = (game.to_fen(), game.board.hash());
+        // no limit on the moves taken back
+        let mut regretted = 0;
+        while game.regret() {
+            regretted += 1;
+        }
+        assert_eq!(regretted, 6);
+        assert!(game.history.is_empty());
+        assert_eq!((game.to_fen(), game.board.hash()), start);
+        // and all of them come back
+        let mut redone = 0;
+        while game.redo() {
+            redone += 1;
+        }
+        assert_eq!(redone, 6);
+        assert_eq!((game.to_fen(), game.board.hash()), end);
+    }
+
+    #[test]
+    fn a_new_move_drops_the_regretted_ones() {
+        let mut game = ChessGame::new();
+        play_iccs(&mut game, &["h2e2", "h7e7", "e2e6"]);
+        assert!(game.regret());
+        assert!(game.regret());
+        play_iccs(&mut game, &["h9g7"]);
+        assert!(!game.redo());
+        assert_eq!(game.history.len(), 2);
+    }
+
+    #[test]
+    fn no_redo_once_the_game_is_decided() {
+        let mut game = ChessGame::new();
+        play_iccs(&mut game, &["h2e2", "h7e7", "e2e6"]);
+        assert!(game.regret());
+        assert!(game.regret());
+        assert!(game.redo());
+        game.time_out(PlayerColor::Black);
+        assert!(!game.redo());
+        assert_eq!(game.history.len(), 2);
+    }
+}
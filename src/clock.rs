@@ -0,0 +1,173 @@
+// Chess clock for timed games. The time is driven from outside with `tick`,
+// so the same clock works with a frame timer, a wall clock or in tests.
+use crate::{attacks::side, pieces::PlayerColor};
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeControl {
+    // the whole game has to be played in `base`
+    SuddenDeath { base: Duration },
+    // `increment` is added after every move
+    Fischer { base: Duration, increment: Duration },
+    // after `base`, every move has to be made within `period`,
+    // each period running out uses one of `periods`
+    ByoYomi { base: Duration, period: Duration, periods: u32 },
+}
+
+impl TimeControl {
+    fn base(&self) -> Duration {
+        match *self {
+            TimeControl::SuddenDeath { base } => base,
+            TimeControl::Fischer { base, .. } => base,
+            TimeControl::ByoYomi { base, .. } => base,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub control: TimeControl,
+    // main time, or the current period once in byo-yomi
+    remaining: [Duration; 2],
+    // byo-yomi periods not used up yet, None while still on main time
+    periods: [Option<u32>; 2],
+    running: Option<PlayerColor>,
+    flagged: Option<PlayerColor>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock {
+            control,
+            remaining: [control.base(); 2],
+            periods: [None; 2],
+            running: None,
+            flagged: None,
+        }
+    }
+
+    pub fn remaining(&self, color: PlayerColor) -> Duration {
+        self.remaining[side(color)]
+    }
+
+    // None while `color` is still on main time
+    pub fn periods(&self, color: PlayerColor) -> Option<u32> {
+        self.periods[side(color)]
+    }
+
+    pub fn running(&self) -> Option<PlayerColor> {
+        self.running
+    }
+
+    // the side whose time ran out
+    pub fn flagged(&self) -> Option<PlayerColor> {
+        self.flagged
+    }
+
+    pub fn start(&mut self, color: PlayerColor) {
+        if self.flagged.is_none() {
+            self.running = Some(color);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.running = None;
+    }
+
+    pub fn tick(&mut self, elapsed: Duration) {
+        let Some(color) = self.running else { return };
+        let s = side(color);
+        let mut elapsed = elapsed;
+        while elapsed >= self.remaining[s] {
+            elapsed -= self.remaining[s];
+            self.remaining[s] = Duration::ZERO;
+            let TimeControl::ByoYomi { period, periods, .. } = self.control else {
+                return self.flag(color);
+            };
+            // main time is over, or one more period
+            let left = match self.periods[s] {
+                None => periods,
+                Some(left) => left.saturating_sub(1),
+            };
+            if left == 0 || period.is_zero() {
+                self.periods[s] = Some(0);
+                return self.flag(color);
+            }
+            self.periods[s] = Some(left);
+            self.remaining[s] = period;
+        }
+        self.remaining[s] -= elapsed;
+    }
+
+    fn flag(&mut self, color: PlayerColor) {
+        self.flagged = Some(color);
+        self.running = None;
+    }
+
+    // the running side has moved, the other side's time starts
+    pub fn press(&mut self) {
+        let Some(color) = self.running else { return };
+        let s = side(color);
+        match self.control {
+            TimeControl::SuddenDeath { .. } => {}
+            TimeControl::Fischer { increment, .. } => self.remaining[s] += increment,
+            TimeControl::ByoYomi { period, .. } => {
+                if self.periods[s].is_some() {
+                    self.remaining[s] = period;
+                }
+            }
+        }
+        self.running = Some(color.next());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn sudden_death_flags() {
+        let mut clock = Clock::new(TimeControl::SuddenDeath { base: SECOND * 10 });
+        clock.start(PlayerColor::Red);
+        clock.tick(SECOND * 4);
+        clock.press();
+        clock.tick(SECOND * 20);
+        assert_eq!(clock.remaining(PlayerColor::Red), SECOND * 6);
+        assert_eq!(clock.flagged(), Some(PlayerColor::Black));
+        assert_eq!(clock.running(), None);
+    }
+
+    #[test]
+    fn fischer_adds_the_increment() {
+        let mut clock = Clock::new(TimeControl::Fischer { base: SECOND * 10, increment: SECOND * 3 });
+        clock.start(PlayerColor::Red);
+        clock.tick(SECOND * 2);
+        clock.press();
+        assert_eq!(clock.remaining(PlayerColor::Red), SECOND * 11);
+        assert_eq!(clock.running(), Some(PlayerColor::Black));
+        // paused, nobody's time goes
+        clock.pause();
+        clock.tick(SECOND * 100);
+        assert_eq!(clock.remaining(PlayerColor::Black), SECOND * 10);
+    }
+
+    #[test]
+    fn byo_yomi_uses_up_periods() {
+        let mut clock = Clock::new(TimeControl::ByoYomi { base: SECOND * 10, period: SECOND * 5, periods: 2 });
+        clock.start(PlayerColor::Red);
+        clock.tick(SECOND * 12);
+        assert_eq!(clock.periods(PlayerColor::Red), Some(2));
+        assert_eq!(clock.remaining(PlayerColor::Red), SECOND * 3);
+        // moving in time gives the whole period back
+        clock.press();
+        assert_eq!(clock.remaining(PlayerColor::Red), SECOND * 5);
+        clock.press();
+        clock.tick(SECOND * 7);
+        assert_eq!(clock.periods(PlayerColor::Red), Some(1));
+        assert_eq!(clock.flagged(), None);
+        clock.tick(SECOND * 3);
+        assert_eq!(clock.flagged(), Some(PlayerColor::Red));
+    }
+}
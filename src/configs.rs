@@ -1,14 +1,41 @@
-// window
+use chinese_chess::{clock::TimeControl, pieces::PlayerColor, repetition::Ruleset};
+
+// window size the layout is made for, it scales to the actual window
 pub const WH: usize = 600;
-pub const WW: usize = 520;
+pub const WW: usize = 760;
 
-// board
+// board, in world units of that layout
 pub const GL: f32 = 50.0; // grid length
-pub const BCX: f32 = 0.0; // board center x
+pub const BCX: f32 = -88.0; // board center x, its picture fills the left 583 units, the move list the right 170
 pub const BCY: f32 = 20.0; // board center y
-// board grid point count
-pub const BW: usize = 9; // board width, horizontal axis
-pub const BH: usize = 10; // board height, vertical axis
 
-// Max regret steps
-pub const MAX_REGRET: usize = 4;
\ No newline at end of file
+// a font in assets with chinese characters, e.g. Some("fonts/NotoSansSC-Bold.otf"),
+// shows the move list in chinese notation rather than WXF
+pub const MOVE_LIST_FONT: Option<&str> = None;
+
+// seconds between the moves when a replayed game plays itself
+pub const REPLAY_INTERVAL: f32 = 1.5;
+
+// ctrl+s saves the game here (and next to it as .xqf), ctrl+o loads it
+pub const SAVE_PATH: &str = "game.pgn";
+
+// how repeated positions are judged, perpetual check and chase lose under both
+pub const RULESET: Ruleset = Ruleset::Asian;
+// moves per side without a capture or a bing going forward that draw the game, None for no limit
+pub const MOVE_LIMIT: Option<u32> = Some(60);
+
+// computer opponent, None for two human players
+pub const AI_COLOR: Option<PlayerColor> = Some(PlayerColor::Black);
+// plies searched by the computer opponent
+pub const AI_DEPTH: u32 = 3;
+// a local UCCI engine, e.g. Some("/usr/local/bin/pikafish"), plays AI_COLOR in place of the built-in search
+pub const ENGINE_PATH: Option<&str> = None;
+// handshake sent to the engine, "uci" for engines speaking UCI such as Pikafish
+pub const ENGINE_PROTOCOL: &str = "ucci";
+// how the engine is asked for each move
+pub const ENGINE_GO: &str = "go depth 8";
+
+// chess clock, None for untimed games, e.g. ten minutes a side with five seconds added per move:
+// Some(TimeControl::Fischer { base: Duration::from_secs(600), increment: Duration::from_secs(5) })
+// (with std::time::Duration), TimeControl::SuddenDeath and TimeControl::ByoYomi work the same way
+pub const TIME_CONTROL: Option<TimeControl> = None;
@@ -0,0 +1,267 @@
+use crate::{chess::Board, pieces::{Action, Piece, PlayerColor}, pos::Pos};
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use std::time::Instant;
+
+// score of a mate found at the root, mates further away score less
+const MATE: i32 = 100_000;
+
+// positional bonus tables, laid out from red's point of view with y = 0 at
+// red's back rank, index = x + y * 9
+#[rustfmt::skip]
+const MA_PST: [i32; 90] = [
+     0, -3,  2,  0,  2,  0,  2, -3,  0,
+    -3,  2,  4,  5,-10,  5,  4,  2, -3,
+     5,  4,  6,  7,  4,  7,  6,  4,  5,
+     4,  6, 10,  7, 10,  7, 10,  6,  4,
+     2, 10, 13, 14, 15, 14, 13, 10,  2,
+     2, 12, 11, 15, 16, 15, 11, 12,  2,
+     5, 20, 12, 19, 12, 19, 12, 20,  5,
+     4, 10, 11, 15, 11, 15, 11, 10,  4,
+     2,  8, 15,  9,  6,  9, 15,  8,  2,
+     2,  2,  2,  8,  2,  8,  2,  2,  2,
+];
+
+#[rustfmt::skip]
+const CHE_PST: [i32; 90] = [
+    -2, 10,  6, 14, 12, 14,  6, 10, -2,
+     8,  4,  8, 16,  8, 16,  8,  4,  8,
+     4,  8,  6, 14, 12, 14,  6,  8,  4,
+     6, 10,  8, 14, 14, 14,  8, 10,  6,
+    12, 16, 14, 20, 20, 20, 14, 16, 12,
+    12, 14, 12, 18, 18, 18, 12, 14, 12,
+    12, 18, 16, 22, 22, 22, 16, 18, 12,
+    12, 12, 12, 18, 18, 18, 12, 12, 12,
+    16, 20, 18, 24, 26, 24, 18, 20, 16,
+    14, 14, 12, 18, 16, 18, 12, 14, 14,
+];
+
+#[rustfmt::skip]
+const PAO_PST: [i32; 90] = [
+     0,  0,  1,  3,  3,  3,  1,  0,  0,
+     0,  1,  2,  2,  2,  2,  2,  1,  0,
+     1,  0,  4,  3,  5,  3,  4,  0,  1,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,
+    -1,  0,  3,  0,  4,  0,  3,  0, -1,
+     0,  0,  0,  0,  4,  0,  0,  0,  0,
+     0,  3,  3,  2,  4,  2,  3,  3,  0,
+     1,  1,  0, -5, -4, -5,  0,  1,  1,
+     2,  2,  0, -4, -7, -4,  0,  2,  2,
+     4,  4,  0, -5, -6, -5,  0,  4,  4,
+];
+
+#[rustfmt::skip]
+const BING_PST: [i32; 90] = [
+     0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0, -2,  0,  4,  0, -2,  0,  0,
+     2,  0,  8,  0,  8,  0,  8,  0,  2,
+     6, 12, 18, 18, 20, 18, 18, 12,  6,
+    10, 20, 30, 34, 40, 34, 30, 20, 10,
+    14, 26, 42, 60, 80, 60, 42, 26, 14,
+    18, 36, 56, 80,120, 80, 56, 36, 18,
+     0,  3,  6,  9, 12,  9,  6,  3,  0,
+];
+
+fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Jiang => 0, // never traded, losing it is handled as a mate
+        Piece::Shi => 200,
+        Piece::Xiang => 200,
+        Piece::Ma => 400,
+        Piece::Che => 900,
+        Piece::Pao => 450,
+        Piece::Bing => 100,
+    }
+}
+
+fn position_value(piece: Piece, pos: Pos, color: PlayerColor) -> i32 {
+    // mirror black pieces onto red's side of the tables
+    let y = if color == PlayerColor::Red { pos.1 } else { 9 - pos.1 };
+    let i = (pos.0 + y * 9) as usize;
+    match piece {
+        Piece::Ma => MA_PST[i],
+        Piece::Che => CHE_PST[i],
+        Piece::Pao => PAO_PST[i],
+        Piece::Bing => BING_PST[i],
+        _ => 0,
+    }
+}
+
+// static evaluation from the point of view of `color`
+pub fn evaluate(board: &Board, color: PlayerColor) -> i32 {
+    let mut score = 0;
+    for (pos, c, p) in board.pieces() {
+        let value = piece_value(p) + position_value(p, pos, c);
+        if c == color {
+            score += value;
+        } else {
+            score -= value;
+        }
+    }
+    score
+}
+
+// most valuable victim first, then least valuable attacker
+fn order_moves(board: &Board, moves: &mut [(Pos, Action)]) {
+    moves.sort_by_cached_key(|(from, action)| {
+        if let Action::Take(to) = action {
+            let victim = board.get(*to).unwrap().map_or(0, |(_, p)| piece_value(p));
+            let attacker = board.get(*from).unwrap().map_or(0, |(_, p)| piece_value(p));
+            -(victim * 10 - attacker)
+        } else {
+            0
+        }
+    });
+}
+
+// when to give up searching deeper
+#[derive(Clone, Default)]
+pub struct Limits {
+    // plies, searched one after another starting from 1
+    pub depth: u32,
+    pub deadline: Option<Instant>,
+    // raised by another thread to end the search early
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+struct Searcher<'a> {
+    limits: &'a Limits,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Searcher<'_> {
+    // checked every few nodes only, the clock is not free
+    fn should_abort(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.aborted && self.nodes & 1023 == 0 {
+            let stopped = self.limits.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+            let timeout = self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            self.aborted = stopped || timeout;
+        }
+        self.aborted
+    }
+
+    // only look at captures until the position is quiet
+    fn quiescence(&mut self, board: &mut Board, color: PlayerColor, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_abort() {
+            return 0;
+        }
+        let stand_pat = evaluate(board, color);
+        if stand_pat >= beta {
+            return beta;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+        let mut captures = vec![];
+        let mut actions = vec![];
+        for (from, c, p) in board.pieces() {
+            if c != color {
+                continue;
+            }
+            actions.clear();
+            p.push_moves(board, from, color, &mut actions);
+            captures.extend(actions.iter().filter(|action| matches!(action, Action::Take(_))).map(|action| (from, *action)));
+        }
+        order_moves(board, &mut captures);
+        for (from, action) in captures {
+            let captured = board.make(from, action);
+            // captures leaving the own jiang en prise are not moves
+            if board.in_check(color) {
+                board.unmake(from, action, captured);
+                continue;
+            }
+            let score = -self.quiescence(board, color.next(), -beta, -alpha);
+            board.unmake(from, action, captured);
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        alpha
+    }
+
+    fn alpha_beta(&mut self, board: &mut Board, color: PlayerColor, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        if depth == 0 {
+            return self.quiescence(board, color, alpha, beta);
+        }
+        if self.should_abort() {
+            return 0;
+        }
+        let mut moves = board.legal_moves(color);
+        if moves.is_empty() {
+            // checkmated or stalemated, both lose in xiangqi
+            return -MATE + ply;
+        }
+        order_moves(board, &mut moves);
+        for (from, action) in moves {
+            let captured = board.make(from, action);
+            let score = -self.alpha_beta(board, color.next(), depth - 1, ply + 1, -beta, -alpha);
+            board.unmake(from, action, captured);
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        alpha
+    }
+}
+
+// iterative deepening, `report` gets (depth, score, best move) after every finished depth.
+// The best move of the last finished depth is returned, None when `color` has no legal move.
+pub fn search(
+    board: &Board,
+    color: PlayerColor,
+    limits: &Limits,
+    mut report: impl FnMut(u32, i32, (Pos, Action)),
+) -> Option<(Pos, Action)> {
+    let mut moves = board.legal_moves(color);
+    order_moves(board, &mut moves);
+    let mut best = moves.first().copied();
+    let mut searcher = Searcher { limits, nodes: 0, aborted: false };
+    let mut board = board.clone();
+    for depth in 1..=limits.depth.max(1) {
+        let mut alpha = -MATE - 1;
+        let beta = MATE + 1;
+        let mut depth_best = None;
+        for (i, (from, action)) in moves.iter().enumerate() {
+            let captured = board.make(*from, *action);
+            let score = -searcher.alpha_beta(&mut board, color.next(), depth - 1, 1, -beta, -alpha);
+            board.unmake(*from, *action, captured);
+            if searcher.aborted {
+                break;
+            }
+            if depth_best.is_none() || score > alpha {
+                alpha = score;
+                depth_best = Some(i);
+            }
+        }
+        if searcher.aborted {
+            break;
+        }
+        if let Some(i) = depth_best {
+            // search the best move first at the next depth
+            let mv = moves.remove(i);
+            moves.insert(0, mv);
+            best = Some(mv);
+            report(depth, alpha, mv);
+            // a forced mate will not get any better
+            if alpha.abs() >= MATE - depth as i32 {
+                break;
+            }
+        }
+    }
+    best
+}
+
+// search `depth` plies ahead and return the best move of `color`,
+// None when `color` has no legal move
+pub fn best_move(board: &Board, color: PlayerColor, depth: u32) -> Option<(Pos, Action)> {
+    search(board, color, &Limits { depth, ..Default::default() }, |_, _, _| {})
+}
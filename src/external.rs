@@ -0,0 +1,146 @@
+// A UCCI (or UCI) engine running as a child process, such as Pikafish.
+// Its output is read on a thread so that asking for a move never blocks the caller.
+use crate::{chess::ChessGame, notation, pieces::Action, pos::Pos};
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{mpsc::{self, Receiver, TryRecvError}, Mutex},
+    thread,
+};
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(io::Error),
+    // the engine found no move to play
+    NoMove,
+    // the move is not legal in the position it was asked about
+    IllegalMove(String),
+    Exited,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io(err) => write!(f, "{}", err),
+            EngineError::NoMove => write!(f, "engine has no move"),
+            EngineError::IllegalMove(mv) => write!(f, "engine played illegal move '{}'", mv),
+            EngineError::Exited => write!(f, "engine exited"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+    fn from(err: io::Error) -> Self {
+        EngineError::Io(err)
+    }
+}
+
+pub struct ExternalEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Mutex<Receiver<String>>,
+    // go commands whose bestmove is still to come
+    pending: usize,
+    // of those, the ones given up on by `cancel`
+    stale: usize,
+}
+
+impl ExternalEngine {
+    // `protocol` is the handshake command, "ucci" or "uci"
+    pub fn launch(path: &str, protocol: &str) -> io::Result<ExternalEngine> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = ExternalEngine {
+            child,
+            stdin,
+            lines: Mutex::new(receiver),
+            pending: 0,
+            stale: 0,
+        };
+        engine.send(protocol)?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    // send the game so far and ask for a move with `go`, e.g. "go depth 8"
+    pub fn go(&mut self, game: &ChessGame, go: &str) -> io::Result<()> {
+        let mut position = format!("position fen {}", game.start_fen);
+        if !game.history.is_empty() {
+            position.push_str(" moves");
+            for m in game.history.iter() {
+                position.push(' ');
+                position.push_str(&notation::to_iccs(m.from, m.action));
+            }
+        }
+        self.send(&position)?;
+        self.send(go)?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    // stop thinking, the move it was thinking about will be ignored
+    pub fn cancel(&mut self) {
+        if self.pending > 0 {
+            let _ = self.send("stop");
+            self.stale += self.pending;
+            self.pending = 0;
+        }
+    }
+
+    // None until the engine answers the last `go`, `game` must still be the position it was asked about
+    pub fn poll(&mut self, game: &ChessGame) -> Option<Result<(Pos, Action), EngineError>> {
+        loop {
+            let line = match self.lines.lock().unwrap().try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(EngineError::Exited)),
+            };
+            let mut words = line.split_whitespace();
+            let answer = match words.next() {
+                Some("bestmove") => words.next(),
+                Some("nobestmove") => None,
+                _ => continue,
+            };
+            if self.stale > 0 {
+                self.stale -= 1;
+                continue;
+            }
+            self.pending = self.pending.saturating_sub(1);
+            let Some(iccs) = answer else {
+                return Some(Err(EngineError::NoMove));
+            };
+            return Some(match notation::from_iccs(&game.board, iccs) {
+                Some((from, action)) if game.playable_move(from, action.target()).is_some() => Ok((from, action)),
+                _ => Err(EngineError::IllegalMove(iccs.to_string())),
+            });
+        }
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
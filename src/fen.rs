@@ -0,0 +1,213 @@
+// Xiangqi FEN, e.g. the opening position:
+// rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1
+// ranks are listed from black's back rank (y = 9) down to red's (y = 0),
+// upper case letters are red pieces and lower case letters black ones.
+use crate::{
+    chess::{Board, ChessGame, BH, BW},
+    pieces::{Piece, PlayerColor},
+    pos::Pos,
+};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FenError {
+    Empty,
+    BadRankCount(usize),
+    BadRankLength(usize),
+    BadPiece(char),
+    BadSide(String),
+    BadCounter(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "empty fen"),
+            FenError::BadRankCount(n) => write!(f, "expected {} ranks, found {}", BH, n),
+            FenError::BadRankLength(y) => write!(f, "rank {} does not have {} files", y, BW),
+            FenError::BadPiece(c) => write!(f, "unknown piece letter '{}'", c),
+            FenError::BadSide(s) => write!(f, "unknown side to move '{}'", s),
+            FenError::BadCounter(s) => write!(f, "bad move counter '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn piece_to_char(color: PlayerColor, piece: Piece) -> char {
+    let c = match piece {
+        Piece::Jiang => 'k',
+        Piece::Shi => 'a',
+        Piece::Xiang => 'b',
+        Piece::Ma => 'n',
+        Piece::Che => 'r',
+        Piece::Pao => 'c',
+        Piece::Bing => 'p',
+    };
+    match color {
+        PlayerColor::Red => c.to_ascii_uppercase(),
+        PlayerColor::Black => c,
+    }
+}
+
+fn char_to_piece(c: char) -> Result<(PlayerColor, Piece), FenError> {
+    let color = if c.is_ascii_uppercase() { PlayerColor::Red } else { PlayerColor::Black };
+    // e and h are the older letters for xiang and ma
+    let piece = match c.to_ascii_lowercase() {
+        'k' => Piece::Jiang,
+        'a' => Piece::Shi,
+        'b' | 'e' => Piece::Xiang,
+        'n' | 'h' => Piece::Ma,
+        'r' => Piece::Che,
+        'c' => Piece::Pao,
+        'p' => Piece::Bing,
+        _ => return Err(FenError::BadPiece(c)),
+    };
+    Ok((color, piece))
+}
+
+impl Board {
+    // reads the piece placement field, black to move goes into the hash,
+    // any following fields are ignored
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let placement = fen.split_whitespace().next().ok_or(FenError::Empty)?;
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != BH {
+            return Err(FenError::BadRankCount(ranks.len()));
+        }
+        let mut board = Board::new();
+        for (row, rank) in ranks.iter().enumerate() {
+            let y = (BH - 1 - row) as i32;
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    x += skip as usize;
+                } else {
+                    if x >= BW {
+                        return Err(FenError::BadRankLength(y as usize));
+                    }
+                    board.set(Pos(x as i32, y), Some(char_to_piece(c)?));
+                    x += 1;
+                }
+            }
+            if x != BW {
+                return Err(FenError::BadRankLength(y as usize));
+            }
+        }
+        if fen.split_whitespace().nth(1) == Some("b") {
+            board.flip_side();
+        }
+        Ok(board)
+    }
+
+    // the piece placement field only
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for y in (0..BH as i32).rev() {
+            let mut empty = 0;
+            for x in 0..BW as i32 {
+                match self.get(Pos(x, y)).unwrap() {
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(*color, *piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+        fen
+    }
+}
+
+impl ChessGame {
+    // side to move, the halfmove clock and the full move number are taken over into
+    // `player`, `quiet_plies` and `turn`, red is assumed to have made the first move of the game
+    pub fn from_fen(fen: &str) -> Result<ChessGame, FenError> {
+        let board = Board::from_fen(fen)?;
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let player = match fields.get(1) {
+            None | Some(&"w") | Some(&"r") => PlayerColor::Red,
+            Some(&"b") => PlayerColor::Black,
+            Some(side) => return Err(FenError::BadSide(side.to_string())),
+        };
+        let quiet_plies = match fields.get(4) {
+            Some(n) => n.parse::<u32>().map_err(|_| FenError::BadCounter(n.to_string()))?,
+            None => 0,
+        };
+        let fullmove = match fields.get(5) {
+            Some(n) => n.parse::<u32>().map_err(|_| FenError::BadCounter(n.to_string()))?.max(1),
+            None => 1,
+        };
+        let mut game = ChessGame::new();
+        game.board = board;
+        game.turn = (fullmove - 1) * 2 + if player == PlayerColor::Black { 1 } else { 0 };
+        game.quiet_plies = quiet_plies;
+        game.player = Some(player);
+        game.start_fen = game.to_fen();
+        Ok(game)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let side = match self.player.unwrap_or(PlayerColor::Red) {
+            PlayerColor::Red => "w",
+            PlayerColor::Black => "b",
+        };
+        format!("{} {} - - {} {}", self.board.to_fen(), side, self.quiet_plies, self.turn / 2 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::START_FEN;
+
+    #[test]
+    fn round_trips() {
+        for fen in [
+            START_FEN,
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 b - - 3 17",
+            "3k5/9/9/9/9/9/R8/9/9/R3K4 w - - 12 40",
+        ] {
+            let game = ChessGame::from_fen(fen).unwrap();
+            assert_eq!(game.to_fen(), fen);
+            assert_eq!(game.start_fen, fen);
+            let placement = fen.split_whitespace().next().unwrap();
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), placement);
+        }
+        let game = ChessGame::from_fen("3k5/9/9/9/9/9/R8/9/9/R3K4 b - - 5 9").unwrap();
+        assert_eq!(game.player, Some(PlayerColor::Black));
+        assert_eq!(game.quiet_plies, 5);
+        assert_eq!(game.turn, 17);
+    }
+
+    #[test]
+    fn short_forms_are_read() {
+        // the side and counters may be left out, r for red and the older e/h letters
+        let game = ChessGame::from_fen("rheakaehr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RHEAKAEHR r").unwrap();
+        assert_eq!(game.to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |fen: &str| ChessGame::from_fen(fen).err();
+        assert_eq!(err(""), Some(FenError::Empty));
+        assert_eq!(err("  "), Some(FenError::Empty));
+        assert_eq!(err("9/9/9 w"), Some(FenError::BadRankCount(3)));
+        assert_eq!(err("3k6/9/9/9/9/9/9/9/9/4K4 w"), Some(FenError::BadRankLength(9)));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4K3 w"), Some(FenError::BadRankLength(0)));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4K3RR w"), Some(FenError::BadRankLength(0)));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4X4 w"), Some(FenError::BadPiece('X')));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4K4 x"), Some(FenError::BadSide("x".to_string())));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4K4 w - - a 1"), Some(FenError::BadCounter("a".to_string())));
+        assert_eq!(err("3k5/9/9/9/9/9/9/9/9/4K4 w - - 0 -1"), Some(FenError::BadCounter("-1".to_string())));
+    }
+}
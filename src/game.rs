@@ -1,16 +1,79 @@
-use crate::{
-    configs::GL,
-    pos::{Pos, PosEntityMap},
+use crate::configs::{WW, WH, GL, BCX, BCY, AI_COLOR, AI_DEPTH, SAVE_PATH, RULESET, MOVE_LIMIT, ENGINE_PATH, ENGINE_PROTOCOL, ENGINE_GO, TIME_CONTROL, MOVE_LIST_FONT, REPLAY_INTERVAL};
+use chinese_chess::{
+    pos::Pos,
     chess::ChessGame,
-    pieces::{PlayerColor, Action},
+    clock::Clock,
+    engine,
+    external::ExternalEngine,
+    notation,
+    pieces::{PlayerColor, Piece, Action},
+    record::GameRecord,
 };
-use bevy::{prelude::*, window::PrimaryWindow};
-
-use std::collections::HashMap;
+use bevy::{prelude::*, ecs::system::{EntityCommands, SystemParam}, render::camera::ScalingMode, window::PrimaryWindow, tasks::{block_on, AsyncComputeTaskPool, Task}};
+use std::{collections::HashMap, path::{Path, PathBuf}, time::Duration};
 
 #[derive(Component)]
 pub struct ChessBoardTexture;
 
+// the board picture with its frame, its grid lines are 8 x 9 grid lengths apart
+const BOARD_PICTURE: Vec2 = Vec2::new(11.65 * GL, 11.44 * GL);
+
+#[derive(Resource, Default)]
+pub struct PosEntityMap(pub HashMap<Pos, Entity>);
+
+// the game being played, placed in the world
+#[derive(Resource, Deref, DerefMut)]
+pub struct Chess {
+    #[deref]
+    pub game: ChessGame,
+    // rotated by 180 degrees, black at the bottom
+    pub flipped: bool,
+}
+
+impl Chess {
+    // the point as seen on the screen, the same mapping works both ways
+    fn oriented(&self, pos: Pos) -> Pos {
+        if self.flipped {
+            Pos(8 - pos.0, 9 - pos.1)
+        } else {
+            pos
+        }
+    }
+
+    // world coordinate of the center of the board
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(BCX, BCY)
+    }
+
+    pub fn in_bound(&self, world_position: &Vec2) -> bool {
+        let on_board_position = *world_position - self.center();
+        if on_board_position.x >= -4.5 * GL && on_board_position.x <= 4.5 * GL &&
+        on_board_position.y >= -5. * GL && on_board_position.y <= 5. * GL {
+                return true;
+        } else {
+            return false;
+        }
+    }
+
+    pub fn world_to_board(&self, world_position: &Vec2) -> Pos {
+        // world position must on board
+        let on_board_position = *world_position - self.center();
+        return self.oriented(Pos {
+            0: ((on_board_position.x + 4.5 * GL) / GL) as i32,
+            1: ((on_board_position.y + 5.0 * GL) / GL) as i32,
+        })
+    }
+
+    pub fn board_to_world(&self, pos: Pos) -> Transform {
+        let pos = self.oriented(pos);
+        Transform::from_xyz(
+            (pos.0 as f32 - 4.0) * GL + self.center().x, 
+            (pos.1 as f32 - 4.5) * GL + self.center().y, 
+            1.0
+        )
+    }
+}
+
 #[derive(Component)]
 struct MainCamera;
 
@@ -21,67 +84,157 @@ const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 fn setup(
     mut commands: Commands, 
     asset_server: Res<AssetServer>,
-    chess: Res<ChessGame>,
+    chess: Res<Chess>,
+    clock: Res<ChessClock>,
 ) {
-    commands.spawn((Camera2dBundle::default(), MainCamera));
+    let mut camera = Camera2dBundle::default();
+    // the layout always fits the window, whatever its size and DPI,
+    // the cursor goes through the same projection back into board coordinates
+    camera.projection.scaling_mode = ScalingMode::AutoMin { min_width: WW as f32, min_height: WH as f32 };
+    commands.spawn((camera, MainCamera));
     commands.spawn((
         SpriteBundle {
             texture: asset_server.load("chessboard.png"),
             sprite: Sprite {
-                custom_size: Some(Vec2::new(520.0, 520.0)),
+                custom_size: Some(BOARD_PICTURE),
                 ..default()
             },
-            transform: Transform::from_translation(chess.board.center.extend(0.0)).with_scale(Vec3::new(1.12, 1.1, 1.0)),
+            transform: Transform::from_translation(chess.center().extend(0.0)),
             ..default()
         },
         ChessBoardTexture,
     ));
-    commands.spawn(NodeBundle {
-        style: Style {
-            position_type: PositionType::Absolute,
-            width: Val::Percent(100.0),
-            height: Val::Px(50.0),
-            bottom: Val::Percent(0.0),
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
+    if clock.clock.is_some() {
+        commands.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(0.0),
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
             ..default()
-        },
-        // background_color: BackgroundColor(Color::RED),
-        ..default()
-    })
-    .with_children(|parent| {
-        for button_text in vec!["regret", "restart"] {
-            parent.spawn(
-                ButtonBundle {
-                    style: Style {
-                        width: Val::Px(150.0),
-                        height: Val::Px(50.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        // horizontally center child text
-                        justify_content: JustifyContent::Center,
-                        // vertically center child text
-                        align_items: AlignItems::Center,
-                        margin: UiRect{left: Val::Px(10.0), ..default()},
-                        ..default()
-                    },
-                    border_color: BorderColor(Color::BLACK),
-                    background_color: NORMAL_BUTTON.into(),
-                    ..default()
-                })
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        button_text,
+        })
+        .with_children(|parent| {
+            for color in [PlayerColor::Red, PlayerColor::Black] {
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
+                            font_size: 24.0,
+                            color: Color::rgb(0.1, 0.1, 0.1),
                         },
-                    ));
-                });
-        }
+                    ),
+                    ClockText(color),
+                ));
+            }
+        });
+    }
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(0.0),
+                top: Val::Px(36.0),
+                bottom: Val::Px(56.0),
+                width: Val::Px(170.0),
+                padding: UiRect::horizontal(Val::Px(6.0)),
+                flex_direction: FlexDirection::Column,
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            ..default()
+        },
+        MoveList,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 24.0,
+                color: Color::rgb(0.1, 0.1, 0.1),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(52.0),
+            left: Val::Px(10.0),
+            display: Display::None,
+            ..default()
+        }),
+        MoveEntryText,
+    ));
+    for replay in [false, true] {
+        let mut bar = commands.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Px(50.0),
+                bottom: Val::Percent(0.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                display: if replay { Display::None } else { Display::Flex },
+                ..default()
+            },
+            // background_color: BackgroundColor(Color::RED),
+            ..default()
+        });
+        bar.insert(ButtonBar { replay });
+        bar.with_children(|parent| {
+            if replay {
+                for button_text in ["|<", "<", ">", ">|", "auto", "flip", "exit"] {
+                    spawn_button(parent, button_text, &asset_server);
+                }
+                return;
+            }
+            for button_text in ["regret", "redo", "restart", "flip", "replay", "resign", "offer draw", "accept", "decline"] {
+                let mut button = spawn_button(parent, button_text, &asset_server);
+                if button_text == "accept" || button_text == "decline" {
+                    button.insert(DrawAnswer);
+                }
+            }
+        });
+    }
+}
+
+fn spawn_button<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, button_text: &str, asset_server: &AssetServer) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn(
+        ButtonBundle {
+            style: Style {
+                height: Val::Px(40.0),
+                padding: UiRect::horizontal(Val::Px(6.0)),
+                border: UiRect::all(Val::Px(3.0)),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                margin: UiRect{left: Val::Px(4.0), ..default()},
+                ..default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            background_color: NORMAL_BUTTON.into(),
+            ..default()
+        });
+    button.with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            button_text,
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        ));
     });
+    button
 }
 
+// what a button or its keyboard shortcut asks for, named by the button's label
+#[derive(Event)]
+struct UiCommand(String);
+
 fn button_system(
     mut interaction_query: Query<
         (
@@ -90,11 +243,10 @@ fn button_system(
             &mut BorderColor,
             &Children,
         ),
-        (Changed<Interaction>, With<Button>),
+        (Changed<Interaction>, With<Button>, Without<PlyButton>),
     >,
     text_query: Query<&Text>,
-    mut game: ResMut<Game>,
-    mut chess: ResMut<ChessGame>,
+    mut ui_commands: EventWriter<UiCommand>,
 ) {
     for (interaction, mut color, mut border_color, children) in &mut interaction_query {
         let text = text_query.get(children[0]).unwrap();
@@ -104,14 +256,7 @@ fn button_system(
                 info!("Press {}", text.sections[0].value);
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
-                if text.sections[0].value == "restart" {
-                    game.state = GameState::Starting;
-                    chess.restart();
-                } else if text.sections[0].value == "regret" {
-                    if chess.regret() {
-                        game.state = GameState::Starting;
-                    }
-                }
+                ui_commands.send(UiCommand(text.sections[0].value.clone()));
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
@@ -125,10 +270,149 @@ fn button_system(
     }
 }
 
+// the position looked at instead of the game and the replay stepping through it
+#[derive(SystemParam)]
+struct Looking<'w> {
+    review: ResMut<'w, Review>,
+    replay: ResMut<'w, Replay>,
+}
+
+fn command_system(
+    mut ui_commands: EventReader<UiCommand>,
+    mut game: ResMut<Game>,
+    mut chess: ResMut<Chess>,
+    mut computer: Computer,
+    mut clock: ResMut<ChessClock>,
+    pointer: Pointer,
+    looking: Looking,
+) {
+    let Pointer { mut selected, mut dragging } = pointer;
+    let Looking { mut review, mut replay } = looking;
+    for UiCommand(command) in ui_commands.read() {
+        // the position the computer was thinking about is gone
+        computer.stop();
+        // and so are the piece held with the mouse and a reply not yet played
+        // when the pieces are set up again
+        if ["restart", "regret", "redo", "replay", "exit"].contains(&command.as_str()) {
+            dragging.0 = None;
+            game.to_play = None;
+        }
+        if game.state == GameState::Replaying {
+            let last = chess.history.len();
+            let ply = review.0.unwrap_or(last);
+            let to = match command.as_str() {
+                "|<" => 0,
+                "<" => ply.saturating_sub(1),
+                ">" => (ply + 1).min(last),
+                ">|" => last,
+                "auto" => {
+                    replay.autoplay = !replay.autoplay;
+                    replay.timer.reset();
+                    ply
+                }
+                "flip" => {
+                    chess.flipped = !chess.flipped;
+                    ply
+                }
+                "exit" => {
+                    // back to the game that was being played
+                    replay.autoplay = false;
+                    if let Some((live, live_clock)) = replay.live.take() {
+                        chess.game = live;
+                        *clock = live_clock;
+                    }
+                    review.set_if_neq(Review(None));
+                    game.state = GameState::Starting;
+                    chess.set_changed();
+                    continue;
+                }
+                _ => ply,
+            };
+            review.set_if_neq(Review(Some(to)));
+            continue;
+        }
+        // and the buttons act on the game, not on the position looked at
+        review.set_if_neq(Review(None));
+        if command == "flip" {
+            chess.flipped = !chess.flipped;
+            selected.0 = None;
+        } else if command == "restart" {
+            game.state = GameState::Starting;
+            chess.restart();
+            clock.reset();
+        } else if command == "replay" {
+            let path = record_path();
+            match GameRecord::load(&path).and_then(|record| record.to_game()) {
+                Ok(loaded) => {
+                    // kept aside until the replay ends
+                    let live = std::mem::replace(&mut chess.game, with_rules(loaded));
+                    replay.live = Some((live, std::mem::replace(&mut *clock, ChessClock::new())));
+                    selected.0 = None;
+                    game.to_play = None;
+                    game.state = GameState::Replaying;
+                    review.0 = Some(0);
+                    replay.autoplay = false;
+                }
+                Err(err) => error!("cannot load {}: {}", path.display(), err),
+            }
+        } else if (command == "regret" || command == "redo") && clock.flagged() {
+            // the time ran out, taking back the move would not bring it back
+            // and nothing is played after it
+            info!("lost on time, the moves can not be taken back or redone");
+        } else if command == "regret" {
+            clock.hold();
+            if chess.regret() {
+                // take back the computer's reply together with our move
+                if game.ai_to_move(&chess) {
+                    chess.regret();
+                }
+                game.state = GameState::Starting;
+            }
+        } else if command == "redo" {
+            clock.hold();
+            if chess.redo() {
+                // and the computer's reply that was taken back with it
+                if game.ai_to_move(&chess) {
+                    chess.redo();
+                }
+                game.state = GameState::Starting;
+            }
+        } else if game.state == GameState::Finished {
+            // nothing left to resign or offer
+        } else if command == "resign" {
+            let color = game.human_side(&chess);
+            chess.resign(color);
+        } else if command == "offer draw" {
+            let color = game.human_side(&chess);
+            chess.offer_draw(color);
+            if game.ai == Some(color.next()) {
+                // the computer takes a draw when it stands worse
+                if engine::evaluate(&chess.board, color.next()) < 0 {
+                    chess.accept_draw(color.next());
+                } else {
+                    info!("{} declines the draw", color.next());
+                    chess.decline_draw();
+                }
+            }
+        } else if command == "accept" {
+            let color = game.human_side(&chess);
+            chess.accept_draw(color);
+        } else if command == "decline" {
+            // only the side the draw was offered to
+            if chess.draw_offer == Some(game.human_side(&chess).next()) {
+                chess.decline_draw();
+            }
+        }
+    }
+}
+
 #[derive(PartialEq)]
 enum GameState {
     Playing,
     Starting,
+    Finished,
+    // stepping through a loaded game, nobody moves and the clock stands
+    Replaying,
 }
 
 impl Default for GameState {
@@ -142,64 +426,383 @@ struct Game {
     to_play: Option<(Pos, Action)>,
     state: GameState,
     last_move_time: f32,
+    ai: Option<PlayerColor>,
+}
+
+impl Game {
+    fn ai_to_move(&self, chess: &ChessGame) -> bool {
+        // red moves first when nobody has moved yet
+        self.ai.is_some() && self.ai == Some(chess.player.unwrap_or(PlayerColor::Red))
+    }
+
+    // who resigns or offers a draw: the player facing the computer, else the side to move
+    fn human_side(&self, chess: &ChessGame) -> PlayerColor {
+        match self.ai {
+            Some(ai) => ai.next(),
+            None => chess.player.unwrap_or(PlayerColor::Red),
+        }
+    }
+}
+
+// the buttons answering a draw offer, shown only while one is open to the player
+#[derive(Component)]
+struct DrawAnswer;
+
+fn draw_answer_system(
+    mut query: Query<&mut Style, With<DrawAnswer>>,
+    game: Res<Game>,
+    chess: Res<Chess>,
+) {
+    if !chess.is_changed() {
+        return;
+    }
+    // to the side the draw was offered to, in a two player game once it is to move
+    let open = chess.draw_offer.is_some_and(|color| color != game.human_side(&chess));
+    let display = if open { Display::Flex } else { Display::None };
+    for mut style in query.iter_mut() {
+        style.display = display;
+    }
 }
 
 #[derive(Resource, Default)]
 struct SelectedSquare(Option<Pos>);
 
-fn mouse_click_system(
-    buttons: Res<Input<MouseButton>>,
-    mut selected: ResMut<SelectedSquare>,
+// the buttons and texts grow and shrink with the board
+fn ui_scale_system(
+    q_window: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let Ok(window) = q_window.get_single() else { return };
+    let scale = (window.width() / WW as f32).min(window.height() / WH as f32) as f64;
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+}
+
+// the position after that many moves is looked at instead of the game, None for the game
+#[derive(Resource, Default, PartialEq)]
+struct Review(Option<usize>);
+
+#[derive(Component)]
+struct MoveList;
+
+// a move in the list, clicking it shows the position after it
+#[derive(Component)]
+struct PlyButton(usize);
+
+// full moves shown, the list follows the last move or the one looked at
+const MOVE_LIST_ROWS: usize = 22;
+
+// every move of the game by its number, in chinese or WXF notation
+fn move_names(chess: &ChessGame) -> Vec<String> {
+    let mut board = chess.board_at(0);
+    chess
+        .history
+        .iter()
+        .map(|m| {
+            let name = match MOVE_LIST_FONT {
+                Some(_) => notation::to_chinese(&board, m.from, m.action),
+                None => notation::to_wxf(&board, m.from, m.action),
+            };
+            board.make(m.from, m.action);
+            name.unwrap_or_else(|| notation::to_iccs(m.from, m.action))
+        })
+        .collect()
+}
+
+fn move_list_system(
+    mut commands: Commands,
+    query: Query<Entity, With<MoveList>>,
+    chess: Res<Chess>,
+    review: Res<Review>,
+    asset_server: Res<AssetServer>,
+) {
+    if !chess.is_changed() && !review.is_changed() {
+        return;
+    }
+    let Ok(list) = query.get_single() else { return };
+    commands.entity(list).despawn_descendants();
+    let font = asset_server.load(MOVE_LIST_FONT.unwrap_or("fonts/FiraSans-Bold.ttf"));
+    let text_style = TextStyle { font, font_size: 18.0, color: Color::rgb(0.1, 0.1, 0.1) };
+    // a game started from a position with black to move has an empty first red move
+    let black_first = chess.history.first().is_some_and(|m| {
+        matches!(chess.board_at(0).get(m.from), Some(Some((PlayerColor::Black, _))))
+    });
+    let offset = black_first as usize;
+    let names = move_names(&chess);
+    let rows = (names.len() + offset).div_ceil(2);
+    let focus = review.0.unwrap_or(names.len()).max(1);
+    let last_row = ((focus - 1 + offset) / 2 + 1).max(MOVE_LIST_ROWS).min(rows);
+    commands.entity(list).with_children(|parent| {
+        for row in last_row.saturating_sub(MOVE_LIST_ROWS)..last_row {
+            parent.spawn(NodeBundle {
+                style: Style { height: Val::Px(22.0), align_items: AlignItems::Center, ..default() },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(format!("{}.", row + 1), text_style.clone())
+                    .with_style(Style { width: Val::Px(34.0), ..default() }));
+                for i in [row * 2, row * 2 + 1] {
+                    // index into the history
+                    let Some(index) = i.checked_sub(offset).filter(|index| *index < names.len()) else {
+                        parent.spawn(NodeBundle { style: Style { width: Val::Px(62.0), ..default() }, ..default() });
+                        continue;
+                    };
+                    let ply = index + 1;
+                    let color = if review.0 == Some(ply) { PRESSED_BUTTON } else { Color::NONE };
+                    parent.spawn((
+                        ButtonBundle {
+                            style: Style { width: Val::Px(62.0), padding: UiRect::horizontal(Val::Px(3.0)), ..default() },
+                            background_color: color.into(),
+                            ..default()
+                        },
+                        PlyButton(ply),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(names[index].clone(), text_style.clone()));
+                    });
+                }
+            });
+        }
+    });
+}
+
+// clicking a move looks at the position after it, clicking the last move goes back to the game
+// unless it is being replayed
+fn move_list_click_system(
+    mut interaction_query: Query<(&Interaction, &PlyButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut review: ResMut<Review>,
+    game: Res<Game>,
+    chess: Res<Chess>,
+) {
+    for (interaction, ply, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                let replaying = game.state == GameState::Replaying;
+                review.set_if_neq(Review(Some(ply.0).filter(|ply| replaying || *ply < chess.history.len())));
+            }
+            Interaction::Hovered => *color = HOVERED_BUTTON.with_a(0.3).into(),
+            Interaction::None => {
+                *color = if review.0 == Some(ply.0) { PRESSED_BUTTON } else { Color::NONE }.into();
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+struct Replay {
+    // the moves follow each other by themselves
+    autoplay: bool,
+    timer: Timer,
+    // the game being played and its clock, put back when the replay ends
+    live: Option<(ChessGame, ChessClock)>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay { autoplay: false, timer: Timer::from_seconds(REPLAY_INTERVAL, TimerMode::Repeating), live: None }
+    }
+}
+
+// the buttons below the board, one bar for playing and one for replaying
+#[derive(Component)]
+struct ButtonBar {
+    replay: bool,
+}
+
+fn button_bar_system(
+    mut query: Query<(&mut Style, &ButtonBar)>,
+    game: Res<Game>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    let replaying = game.state == GameState::Replaying;
+    for (mut style, bar) in query.iter_mut() {
+        style.display = if bar.replay == replaying { Display::Flex } else { Display::None };
+    }
+}
+
+fn replay_system(
+    mut replay: ResMut<Replay>,
+    mut review: ResMut<Review>,
+    game: Res<Game>,
+    chess: Res<Chess>,
+    time: Res<Time>,
+) {
+    if game.state != GameState::Replaying || !replay.autoplay {
+        return;
+    }
+    if !replay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let ply = review.0.unwrap_or(0);
+    if ply < chess.history.len() {
+        review.0 = Some(ply + 1);
+    } else {
+        replay.autoplay = false;
+    }
+}
+
+// a piece held down with the mouse
+struct Drag {
+    from: Pos,
+    press: Vec2,
+    // dragged far enough not to be a click
+    moved: bool,
+    // the legal target under the cursor
+    hover: Option<Pos>,
+}
+
+#[derive(Resource, Default)]
+struct Dragging(Option<Drag>);
+
+#[derive(Component)]
+struct HoverMarker;
+
+// the piece picked on the board, by clicking it or holding it with the mouse
+#[derive(SystemParam)]
+struct Pointer<'w> {
+    selected: ResMut<'w, SelectedSquare>,
+    dragging: ResMut<'w, Dragging>,
+}
+
+#[derive(SystemParam)]
+struct Mouse<'w, 's> {
+    buttons: Res<'w, Input<MouseButton>>,
     // query to get the window (so we can read the current cursor position)
-    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
     // query to get camera transform
-    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    mut game: ResMut<Game>,
-    chess: Res<ChessGame>,
-) {
-    if buttons.just_released(MouseButton::Left) {
+    q_camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+}
+
+impl Mouse<'_, '_> {
+    // the cursor in world coordinates, None outside the window
+    fn position(&self) -> Option<Vec2> {
         // get the camera info and transform
         // assuming there is exactly one main camera entity, so Query::single() is OK
-        let (camera, camera_transform) = q_camera.single();
+        let (camera, camera_transform) = self.q_camera.single();
 
         // There is only one primary window, so we can similarly get it from the query:
-        let window = q_window.single();
+        let window = self.q_window.single();
 
         // check if the cursor is inside the window and get its position
         // then, ask bevy to convert into world coordinates, and truncate to discard Z
-        if let Some(world_position) = window
+        window
             .cursor_position()
             .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
             .map(|ray| ray.origin.truncate())
-        {
-            // info!("World coords: {}/{}", world_position.x, world_position.y);
-            if chess.in_bound(&world_position) {
-                let pos = chess.world_to_board(&world_position);
-                if let Some(old_pos) = selected.0 {
-                    if let Some(Some((o_c, _))) = chess.board.get(old_pos) {
-                        if let Some(Some((c, _))) = chess.board.get(pos) {
-                            // same color piece selection
-                            if *o_c == *c {
-                                selected.0 = Some(pos);
-                                return;
-                            }
-                        }
-                    }
-                    if let Some(action) = chess.playable_move(old_pos, pos) {
-                        game.to_play = Some((old_pos, action));
-                        info!("Goto ({}, {})", pos.0, pos.1);
-                        selected.0 = None;
-                    } else {
-                        selected.0 = None;
+    }
+}
+
+// the piece sprites, moved by hand while dragging, and the marker under the dragged one
+#[derive(SystemParam)]
+struct PieceSprites<'w, 's> {
+    piece_ents: Res<'w, PosEntityMap>,
+    q_transform: Query<'w, 's, &'static mut Transform>,
+    q_hover: Query<'w, 's, Entity, With<HoverMarker>>,
+}
+
+fn mouse_click_system(
+    mut commands: Commands,
+    mouse: Mouse,
+    pointer: Pointer,
+    sprites: PieceSprites,
+    mut game: ResMut<Game>,
+    chess: Res<Chess>,
+    review: Res<Review>,
+) {
+    let Pointer { mut selected, mut dragging } = pointer;
+    let PieceSprites { piece_ents, mut q_transform, q_hover } = sprites;
+    let buttons = &mouse.buttons;
+    if game.state == GameState::Finished || game.ai_to_move(&chess) || review.0.is_some() {
+        selected.0 = None;
+        // a piece held when the game ended goes back
+        if let Some(drag) = dragging.0.take().filter(|drag| drag.moved) {
+            if let Some(ent) = piece_ents.0.get(&drag.from) {
+                commands.entity(*ent).insert(MovingTo(chess.board_to_world(drag.from)));
+            }
+            for ent in q_hover.iter() {
+                commands.entity(ent).despawn();
+            }
+        }
+        return;
+    }
+    let cursor = mouse.position();
+
+    if buttons.just_pressed(MouseButton::Left) {
+        if let Some(world_position) = cursor.filter(|position| chess.in_bound(position)) {
+            let pos = chess.world_to_board(&world_position);
+            let own = matches!(chess.board.get(pos), Some(Some((c, _))) if game.ai != Some(*c));
+            if own && chess.playable_moves(pos).is_some() {
+                dragging.0 = Some(Drag { from: pos, press: world_position, moved: false, hover: None });
+            }
+        }
+    }
+    if dragging.0.as_ref().is_some_and(|drag| !piece_ents.0.contains_key(&drag.from)) {
+        // the pieces were set up again under the held one
+        dragging.0 = None;
+    }
+    if dragging.0.is_none() {
+        for ent in q_hover.iter() {
+            commands.entity(ent).despawn();
+        }
+    }
+    if let Some(drag) = dragging.0.as_mut() {
+        let ent = piece_ents.0[&drag.from];
+        if buttons.pressed(MouseButton::Left) {
+            let Some(world_position) = cursor else { return };
+            if !drag.moved && world_position.distance(drag.press) > GL / 5. {
+                drag.moved = true;
+                // the legal moves are shown while dragging
+                selected.0 = Some(drag.from);
+            }
+            if drag.moved {
+                if let Ok(mut transform) = q_transform.get_mut(ent) {
+                    // above the other pieces
+                    transform.translation = world_position.extend(2.0);
+                }
+                let hover = Some(chess.world_to_board(&world_position))
+                    .filter(|to| chess.in_bound(&world_position) && chess.playable_move(drag.from, *to).is_some());
+                if hover != drag.hover {
+                    drag.hover = hover;
+                    for ent in q_hover.iter() {
+                        commands.entity(ent).despawn();
                     }
-                } else {
-                    if let Some(Some((_, p))) = chess.board.get(pos) {
-                        selected.0 = Some(pos);
-                        info!("selecting {}", p);
-                    } else {
-                        selected.0 = None;
+                    if let Some(to) = hover {
+                        commands.spawn(marker(None, Color::rgba(0.1, 0.6, 0.2, 0.45), GL * 0.95, chess.board_to_world(to), 0.3))
+                            .insert(HoverMarker);
                     }
                 }
+            }
+            return;
+        }
+        let drag = dragging.0.take().unwrap();
+        for ent in q_hover.iter() {
+            commands.entity(ent).despawn();
+        }
+        if drag.moved {
+            let to = cursor.filter(|position| chess.in_bound(position)).map(|position| chess.world_to_board(&position));
+            match to.and_then(|to| chess.playable_move(drag.from, to)) {
+                Some(action) => {
+                    game.to_play = Some((drag.from, action));
+                    info!("Drop on {}", action.target());
+                }
+                // snap back
+                None => {
+                    commands.entity(ent).insert(MovingTo(chess.board_to_world(drag.from)));
+                }
+            }
+            selected.0 = None;
+            return;
+        }
+        // released where it was pressed, a click
+    }
+    if buttons.just_released(MouseButton::Left) {
+        if let Some(world_position) = cursor {
+            // info!("World coords: {}/{}", world_position.x, world_position.y);
+            if chess.in_bound(&world_position) {
+                click(chess.world_to_board(&world_position), &mut selected, &mut game, &chess);
             } else {
                 selected.0 = None;
             }
@@ -207,14 +810,251 @@ fn mouse_click_system(
     }
 }
 
+// a click on the point, or enter on it with the keyboard cursor:
+// select a piece, then play it to the next point chosen
+fn click(pos: Pos, selected: &mut SelectedSquare, game: &mut Game, chess: &Chess) {
+    if let Some(old_pos) = selected.0 {
+        if let Some(Some((o_c, _))) = chess.board.get(old_pos) {
+            if let Some(Some((c, _))) = chess.board.get(pos) {
+                // same color piece selection
+                if *o_c == *c {
+                    selected.0 = Some(pos);
+                    return;
+                }
+            }
+        }
+        if let Some(action) = chess.playable_move(old_pos, pos) {
+            game.to_play = Some((old_pos, action));
+            info!("Goto ({}, {})", pos.0, pos.1);
+            selected.0 = None;
+        } else {
+            selected.0 = None;
+        }
+    } else {
+        if let Some(Some((c, p))) = chess.board.get(pos) {
+            if game.ai == Some(*c) {
+                return;
+            }
+            selected.0 = Some(pos);
+            info!("selecting {}", p);
+        } else {
+            selected.0 = None;
+        }
+    }
+}
+
+// the point picked with the arrow keys, None until one is pressed
+#[derive(Resource, Default)]
+struct KeyCursor(Option<Pos>);
+
+#[derive(Component)]
+struct KeyCursorMarker;
+
+// a move typed in ICCS (h2e2), WXF (C2=5) or chinese notation
+#[derive(Resource, Default)]
+struct MoveEntry {
+    active: bool,
+    text: String,
+    // the text is not a move that can be played
+    invalid: bool,
+}
+
+#[derive(Component)]
+struct MoveEntryText;
+
+fn parse_move(chess: &ChessGame, text: &str) -> Option<(Pos, Action)> {
+    let colors = match chess.player {
+        Some(color) => vec![color],
+        None => vec![PlayerColor::Red, PlayerColor::Black],
+    };
+    let (from, action) = notation::from_iccs(&chess.board, text).or_else(|| {
+        colors.into_iter().find_map(|color| {
+            notation::from_wxf(&chess.board, color, text).or_else(|| notation::from_chinese(&chess.board, color, text))
+        })
+    })?;
+    chess.playable_move(from, action.target()).map(|action| (from, action))
+}
+
+#[derive(SystemParam)]
+struct Keyboard<'w, 's> {
+    keycode: Res<'w, Input<KeyCode>>,
+    chars: EventReader<'w, 's, ReceivedCharacter>,
+}
+
+// the keyboard's cursor and move entry
+#[derive(SystemParam)]
+struct KeyboardPlay<'w> {
+    entry: ResMut<'w, MoveEntry>,
+    cursor: ResMut<'w, KeyCursor>,
+}
+
+// arrows move the cursor, enter selects and plays like a click, escape drops the selection
+// and leaves the move list;
+// f flips the board, ctrl+z / ctrl+y / ctrl+r regret, redo and restart,
+// tab opens the move entry, where enter plays the typed move and escape closes it.
+// A replay steps with left and right, jumps with home and end (or up and down),
+// space starts and stops the autoplay and escape ends it
+fn keyboard_system(
+    keyboard: Keyboard,
+    keyboard_play: KeyboardPlay,
+    mut selected: ResMut<SelectedSquare>,
+    mut ui_commands: EventWriter<UiCommand>,
+    mut game: ResMut<Game>,
+    mut review: ResMut<Review>,
+    chess: Res<Chess>,
+) {
+    let Keyboard { keycode, mut chars } = keyboard;
+    let KeyboardPlay { mut entry, mut cursor } = keyboard_play;
+    if game.state == GameState::Replaying {
+        chars.clear();
+        let command = [
+            (KeyCode::Left, "<"),
+            (KeyCode::Right, ">"),
+            (KeyCode::Home, "|<"),
+            (KeyCode::Up, "|<"),
+            (KeyCode::End, ">|"),
+            (KeyCode::Down, ">|"),
+            (KeyCode::Space, "auto"),
+            (KeyCode::F, "flip"),
+            (KeyCode::Escape, "exit"),
+        ]
+        .into_iter()
+        .find(|(key, _)| keycode.just_pressed(*key));
+        if let Some((_, command)) = command {
+            ui_commands.send(UiCommand(command.to_string()));
+        }
+        return;
+    }
+    let may_move = game.state != GameState::Finished && !game.ai_to_move(&chess) && review.0.is_none();
+    if entry.active {
+        for received in chars.read() {
+            if !received.char.is_control() {
+                entry.text.push(received.char);
+                entry.invalid = false;
+            }
+        }
+        if keycode.just_pressed(KeyCode::Back) {
+            entry.text.pop();
+            entry.invalid = false;
+        } else if keycode.just_pressed(KeyCode::Escape) {
+            *entry = MoveEntry::default();
+        } else if keycode.just_pressed(KeyCode::Return) {
+            match parse_move(&chess, &entry.text).filter(|_| may_move) {
+                Some(to_play) => {
+                    game.to_play = Some(to_play);
+                    selected.0 = None;
+                    *entry = MoveEntry::default();
+                }
+                None => entry.invalid = true,
+            }
+        }
+        return;
+    }
+    // typed while the entry was closed
+    chars.clear();
+    if keycode.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        let command = if keycode.just_pressed(KeyCode::Z) {
+            "regret"
+        } else if keycode.just_pressed(KeyCode::Y) {
+            "redo"
+        } else if keycode.just_pressed(KeyCode::R) {
+            "restart"
+        } else {
+            return;
+        };
+        ui_commands.send(UiCommand(command.to_string()));
+        return;
+    }
+    if keycode.just_pressed(KeyCode::Tab) {
+        entry.active = true;
+    } else if keycode.just_pressed(KeyCode::F) {
+        ui_commands.send(UiCommand("flip".to_string()));
+    } else if keycode.just_pressed(KeyCode::Escape) {
+        selected.0 = None;
+        // back to the game from the move list
+        review.set_if_neq(Review(None));
+    } else if keycode.just_pressed(KeyCode::Return) {
+        if let Some(pos) = cursor.0.filter(|_| may_move) {
+            click(pos, &mut selected, &mut game, &chess);
+        }
+    }
+    let step = [
+        (KeyCode::Left, Pos(-1, 0)),
+        (KeyCode::Right, Pos(1, 0)),
+        (KeyCode::Up, Pos(0, 1)),
+        (KeyCode::Down, Pos(0, -1)),
+    ]
+    .into_iter()
+    .find(|(key, _)| keycode.just_pressed(*key))
+    .map(|(_, step)| step);
+    if let Some(step) = step {
+        // the arrows go the way they point on the screen, the first press only shows the cursor
+        cursor.0 = Some(match cursor.0 {
+            None => selected.0.unwrap_or(chess.oriented(Pos(4, 0))),
+            Some(pos) => {
+                let to = chess.oriented(chess.oriented(pos) + step);
+                Pos(to.0.clamp(0, 8), to.1.clamp(0, 9))
+            }
+        });
+    }
+}
+
+fn key_cursor_system(
+    query: Query<Entity, With<KeyCursorMarker>>,
+    mut commands: Commands,
+    cursor: Res<KeyCursor>,
+    chess: Res<Chess>,
+    asset_server: Res<AssetServer>,
+) {
+    if !cursor.is_changed() && !chess.is_changed() {
+        return;
+    }
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+    if let Some(pos) = cursor.0 {
+        commands.spawn(marker(Some(asset_server.load("circle.png")), Color::rgba(0.2, 0.4, 1.0, 0.9), GL * 1.2, chess.board_to_world(pos), 0.6))
+            .insert(KeyCursorMarker);
+    }
+}
+
+fn move_entry_system(
+    mut query: Query<(&mut Text, &mut Style), With<MoveEntryText>>,
+    entry: Res<MoveEntry>,
+) {
+    if !entry.is_changed() {
+        return;
+    }
+    for (mut text, mut style) in query.iter_mut() {
+        style.display = if entry.active { Display::Flex } else { Display::None };
+        text.sections[0].value = format!("move: {}_", entry.text);
+        text.sections[0].style.color = if entry.invalid { Color::RED } else { Color::rgb(0.1, 0.1, 0.1) };
+    }
+}
+
 #[derive(Component)]
 struct MoveDisplay;
 
+// drawn between the board (z = 0) and the pieces (z = 1)
+fn marker(texture: Option<Handle<Image>>, color: Color, size: f32, mut transform: Transform, z: f32) -> SpriteBundle {
+    transform.translation.z = z;
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::splat(size)),
+            ..Default::default()
+        },
+        texture: texture.unwrap_or_default(),
+        transform,
+        ..Default::default()
+    }
+}
+
 fn display_moves(
     query: Query<Entity, With<MoveDisplay>>,
     mut commands: Commands,
     selected: Res<SelectedSquare>,
-    chess: Res<ChessGame>,
+    chess: Res<Chess>,
     asset_server: Res<AssetServer>,
 ) {
     if selected.is_changed() {
@@ -222,28 +1062,81 @@ fn display_moves(
             commands.entity(move_display).despawn();
         }
         if let Some(pos) = selected.0 {
+            let circle: Handle<Image> = asset_server.load("circle.png");
+            // selection ring around the piece
+            commands.spawn(marker(Some(circle.clone()), Color::rgba(0.1, 0.6, 0.2, 0.8), GL * 1.1, chess.board_to_world(pos), 0.5))
+                .insert(MoveDisplay);
             if let Some(moves) = chess.playable_moves(pos) {
-                let sprite = SpriteBundle {
-                    sprite: Sprite {
-                        color: Color::rgba(0., 0., 0., 0.5),
-                        custom_size: Some(Vec2::new(GL / 2.5, GL / 2.5)),
-                        ..Default::default()
-                    },
-                    texture: asset_server.load("circle.png"),
-                    ..Default::default()
-                };
                 for action in moves {
-                    if let Action::Go(to) = action {
-                        let mut sprite_clone = sprite.clone();
-                        sprite_clone.transform = chess.board_to_world(to);
-                        commands.spawn(sprite_clone).insert(MoveDisplay);
-                    }
+                    let sprite = match action {
+                        Action::Go(to) => marker(Some(circle.clone()), Color::rgba(0., 0., 0., 0.5), GL / 2.5, chess.board_to_world(to), 1.0),
+                        // a red ring under the piece that can be taken
+                        Action::Take(to) => marker(Some(circle.clone()), Color::rgba(0.9, 0.1, 0.1, 0.8), GL * 1.1, chess.board_to_world(to), 0.5),
+                    };
+                    commands.spawn(sprite).insert(MoveDisplay);
                 }
             }
         }
     }
 }
 
+#[derive(Component)]
+struct BoardHighlight;
+
+// the last move's from and to points and a glow under a jiang in check
+fn highlight_system(
+    query: Query<Entity, With<BoardHighlight>>,
+    mut commands: Commands,
+    chess: Res<Chess>,
+    review: Res<Review>,
+    asset_server: Res<AssetServer>,
+) {
+    if !chess.is_changed() && !review.is_changed() {
+        return;
+    }
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+    let (board, last) = match review.0 {
+        Some(ply) => (chess.board_at(ply), ply.checked_sub(1).map(|i| chess.history[i])),
+        None => (chess.board.clone(), chess.history.last().copied()),
+    };
+    if let Some(last) = last {
+        for pos in [last.from, last.action.target()] {
+            commands.spawn(marker(None, Color::rgba(1.0, 0.85, 0.2, 0.45), GL * 0.95, chess.board_to_world(pos), 0.3))
+                .insert(BoardHighlight);
+        }
+    }
+    for color in [PlayerColor::Red, PlayerColor::Black] {
+        if let Some(pos) = board.find_jiang(color).filter(|_| board.in_check(color)) {
+            commands.spawn(marker(Some(asset_server.load("circle.png")), Color::rgba(1.0, 0.0, 0.0, 0.6), GL * 1.35, chess.board_to_world(pos), 0.4))
+                .insert(BoardHighlight);
+        }
+    }
+}
+
+// puts the pieces where they belong after the board was flipped,
+// the markers follow by themselves as they are redrawn
+fn orientation_system(
+    mut query: Query<(&mut Transform, Option<&mut MovingTo>)>,
+    piece_ents: Res<PosEntityMap>,
+    chess: Res<Chess>,
+    mut flipped: Local<bool>,
+) {
+    if *flipped == chess.flipped {
+        return;
+    }
+    *flipped = chess.flipped;
+    for (pos, ent) in piece_ents.0.iter() {
+        if let Ok((mut transform, moving_to)) = query.get_mut(*ent) {
+            *transform = chess.board_to_world(*pos);
+            if let Some(mut moving_to) = moving_to {
+                moving_to.0 = *transform;
+            }
+        }
+    }
+}
+
 #[derive(Component)]
 struct MovingTo(Transform);
 
@@ -254,10 +1147,13 @@ fn play_move(
     mut commands: Commands,
     mut piece_ents: ResMut<PosEntityMap>,
     mut game: ResMut<Game>,
-    mut chess: ResMut<ChessGame>,
+    mut chess: ResMut<Chess>,
+    mut clock: ResMut<ChessClock>,
+    review: Res<Review>,
     time: Res<Time>,
 ) {
-    if time.elapsed_seconds() - game.last_move_time < 1. {
+    // the pieces show an earlier position, the move waits for the way back
+    if time.elapsed_seconds() - game.last_move_time < 1. || review.0.is_some() {
         return;
     }
     if let Some((from, action)) = game.to_play {
@@ -277,7 +1173,15 @@ fn play_move(
         }
         piece_ents.0.remove_entry(&from);
         game.to_play = None;
+        info!(
+            "move {} {} {}",
+            notation::to_chinese(&chess.board, from, action).unwrap_or_default(),
+            notation::to_wxf(&chess.board, from, action).unwrap_or_default(),
+            notation::to_iccs(from, action)
+        );
         chess.play(from, action);
+        clock.moved(&chess);
+        info!("fen: {}", chess.to_fen());
     }
 }
 
@@ -307,44 +1211,426 @@ fn die(mut commands: Commands, query: Query<Entity, With<Die>>) {
     }
 }
 
+fn spawn_piece(commands: &mut Commands, asset_server: &AssetServer, transform: Transform, color: PlayerColor, piece: Piece) -> Entity {
+    let texture = match color {
+        PlayerColor::Red => format!("red/{}.png", piece),
+        PlayerColor::Black => format!("black/{}.png", piece),
+    };
+    commands
+        .spawn(SpriteBundle {
+            texture: asset_server.load(texture),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(GL)),
+                ..Default::default()
+            },
+            transform,
+            ..Default::default()
+        })
+        .id()
+}
+
+// sets the pieces up for a new game or for the position looked at in the move list,
+// going one move forward or back there moves the piece instead
 fn place_pieces(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut piece_ents: ResMut<PosEntityMap>,
     mut game: ResMut<Game>,
-    chess: Res<ChessGame>,
+    chess: Res<Chess>,
+    review: Res<Review>,
+    // the ply the pieces show, None for the game
+    mut shown: Local<Option<usize>>,
 ) {
-    if game.state != GameState::Starting {
+    if game.state != GameState::Starting && !review.is_changed() {
+        return;
+    }
+    let old = shown.unwrap_or(chess.history.len());
+    let ply = review.0.unwrap_or(chess.history.len());
+    *shown = review.0;
+    if game.state != GameState::Starting && !chess.is_changed() && old.abs_diff(ply) == 1 {
+        let m = chess.history[old.min(ply)];
+        let to = m.action.target();
+        if ply > old {
+            let ent = piece_ents.0.remove(&m.from).unwrap();
+            commands.entity(ent).insert(MovingTo(chess.board_to_world(to)));
+            if let Some(o_ent) = piece_ents.0.insert(to, ent) {
+                commands.entity(o_ent).insert(Die);
+            }
+        } else {
+            let ent = piece_ents.0.remove(&to).unwrap();
+            commands.entity(ent).insert(MovingTo(chess.board_to_world(m.from)));
+            piece_ents.0.insert(m.from, ent);
+            // the taken piece comes back
+            if let Some((color, piece)) = m.captured {
+                piece_ents.0.insert(to, spawn_piece(&mut commands, &asset_server, chess.board_to_world(to), color, piece));
+            }
+        }
         return;
     }
     for (_, ent) in piece_ents.0.iter() {
         commands.entity(*ent).despawn();
     }
     piece_ents.0.clear();
-    for (i, grid) in chess.board.points.iter().enumerate() {
-        if let Some((color, piece)) = grid {
-            let pos = chess.board.pos(i);
-            let texture = match color {
-                PlayerColor::Red => format!("red/{}.png", piece),
-                PlayerColor::Black => format!("black/{}.png", piece),
-            };
-            piece_ents.0.insert(
-                pos,
-                commands
-                    .spawn(SpriteBundle {
-                        texture: asset_server.load(texture),
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::splat(GL)),
-                            ..Default::default()
-                        },
-                        transform: chess.board_to_world(pos),
-                        ..Default::default()
-                    })
-                    .id(),
-            );
+    for (pos, color, piece) in chess.board_at(ply).pieces() {
+        piece_ents.0.insert(pos, spawn_piece(&mut commands, &asset_server, chess.board_to_world(pos), color, piece));
+    }
+    if game.state == GameState::Starting {
+        game.state = GameState::Playing;
+    }
+}
+
+#[derive(Resource, Default)]
+struct AiTask(Option<Task<Option<(Pos, Action)>>>);
+
+// the external engine playing in place of the built-in search, if one is configured
+#[derive(Resource)]
+struct ExternalPlayer {
+    engine: Option<ExternalEngine>,
+    thinking: bool,
+}
+
+impl ExternalPlayer {
+    fn launch() -> Self {
+        let engine = ENGINE_PATH.and_then(|path| match ExternalEngine::launch(path, ENGINE_PROTOCOL) {
+            Ok(engine) => Some(engine),
+            Err(err) => {
+                error!("cannot start engine {}: {}", path, err);
+                None
+            }
+        });
+        ExternalPlayer { engine, thinking: false }
+    }
+
+    fn cancel(&mut self) {
+        if let Some(engine) = self.engine.as_mut() {
+            engine.cancel();
+        }
+        self.thinking = false;
+    }
+}
+
+// whoever plays for the computer, the built-in search or the external engine
+#[derive(SystemParam)]
+struct Computer<'w> {
+    ai_task: ResMut<'w, AiTask>,
+    external: ResMut<'w, ExternalPlayer>,
+}
+
+impl Computer<'_> {
+    // forget the move being thought about
+    fn stop(&mut self) {
+        self.ai_task.0 = None;
+        self.external.cancel();
+    }
+}
+
+fn ai_system(
+    mut ai_task: ResMut<AiTask>,
+    mut external: ResMut<ExternalPlayer>,
+    mut game: ResMut<Game>,
+    chess: Res<Chess>,
+    review: Res<Review>,
+) {
+    // the reply waits while an earlier position is looked at,
+    // a button pressed meanwhile may still take back the position it answers
+    let reviewing = review.0.is_some();
+    if external.engine.is_some() {
+        external_engine_system(&mut external, &mut game, &chess, reviewing);
+        return;
+    }
+    if let Some(task) = &ai_task.0 {
+        if task.is_finished() && !reviewing {
+            let best = block_on(ai_task.0.take().unwrap());
+            if game.state == GameState::Playing && game.ai_to_move(&chess) {
+                game.to_play = best;
+            }
+        }
+        return;
+    }
+    if game.state != GameState::Playing || game.to_play.is_some() || !game.ai_to_move(&chess) {
+        return;
+    }
+    let board = chess.board.clone();
+    let color = game.ai.unwrap();
+    ai_task.0 = Some(AsyncComputeTaskPool::get().spawn(async move {
+        engine::best_move(&board, color, AI_DEPTH)
+    }));
+}
+
+fn external_engine_system(
+    external: &mut ExternalPlayer,
+    game: &mut Game,
+    chess: &ChessGame,
+    reviewing: bool,
+) {
+    let engine = external.engine.as_mut().unwrap();
+    let reply = if external.thinking {
+        if reviewing {
+            return;
+        }
+        match engine.poll(chess) {
+            Some(reply) => reply,
+            None => return,
+        }
+    } else {
+        if game.state != GameState::Playing || game.to_play.is_some() || !game.ai_to_move(chess) {
+            return;
         }
+        // the whole game goes along with every request, so the engine always sees the latest move
+        match engine.go(chess, ENGINE_GO) {
+            Ok(()) => external.thinking = true,
+            Err(err) => {
+                error!("engine: {}, the built-in search takes over", err);
+                external.engine = None;
+            }
+        }
+        return;
+    };
+    external.thinking = false;
+    match reply {
+        Ok(best) => {
+            if game.state == GameState::Playing && game.ai_to_move(chess) {
+                game.to_play = Some(best);
+            }
+        }
+        Err(err) => {
+            error!("engine: {}, the built-in search takes over", err);
+            external.engine = None;
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ChessClock {
+    clock: Option<Clock>,
+    // stopped after an undo, until the next move is played
+    held: bool,
+}
+
+impl ChessClock {
+    fn new() -> Self {
+        ChessClock { clock: TIME_CONTROL.map(Clock::new), held: false }
     }
-    game.state = GameState::Playing;
+
+    fn reset(&mut self) {
+        *self = ChessClock::new();
+    }
+
+    fn flagged(&self) -> bool {
+        self.clock.as_ref().is_some_and(|clock| clock.flagged().is_some())
+    }
+
+    fn hold(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.pause();
+        }
+        self.held = true;
+    }
+
+    // the side to move in `chess` is now on the clock
+    fn moved(&mut self, chess: &ChessGame) {
+        let Some(clock) = self.clock.as_mut() else { return };
+        if self.held || clock.running().is_none() {
+            self.held = false;
+            clock.start(chess.player.unwrap_or(PlayerColor::Red));
+        } else {
+            clock.press();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ClockText(PlayerColor);
+
+fn format_clock(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    if seconds < 10 {
+        // tenths count when it gets close
+        format!("{:.1}", remaining.as_secs_f32())
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn clock_system(
+    mut clock: ResMut<ChessClock>,
+    mut game: ResMut<Game>,
+    mut chess: ResMut<Chess>,
+    mut query: Query<(&mut Text, &ClockText)>,
+    time: Res<Time>,
+) {
+    let held = clock.held;
+    let Some(clock) = clock.clock.as_mut() else { return };
+    if game.state == GameState::Finished {
+        clock.pause();
+    } else if game.state == GameState::Playing {
+        if clock.running().is_none() && !held {
+            clock.start(chess.player.unwrap_or(PlayerColor::Red));
+        }
+        clock.tick(time.delta());
+        if let Some(color) = clock.flagged() {
+            if chess.result.is_none() {
+                chess.time_out(color);
+                game.to_play = None;
+            }
+        }
+    }
+    for (mut text, ClockText(color)) in query.iter_mut() {
+        let mut value = format!("{} {}", color, format_clock(clock.remaining(*color)));
+        if let Some(periods) = clock.periods(*color) {
+            value.push_str(&format!(" ({})", periods));
+        }
+        text.sections[0].value = value;
+        text.sections[0].style.color = if clock.running() == Some(*color) {
+            Color::rgb(0.85, 0.1, 0.1)
+        } else {
+            Color::rgb(0.1, 0.1, 0.1)
+        };
+    }
+}
+
+#[derive(Component)]
+struct GameOverText;
+
+fn game_over_system(
+    mut commands: Commands,
+    query: Query<Entity, With<GameOverText>>,
+    asset_server: Res<AssetServer>,
+    mut game: ResMut<Game>,
+    chess: Res<Chess>,
+) {
+    if game.state == GameState::Finished {
+        return;
+    }
+    // restarted or regretted out of a finished game
+    for ent in query.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    if game.state != GameState::Playing || game.to_play.is_some() || !chess.is_changed() {
+        return;
+    }
+    if let Some(outcome) = chess.outcome() {
+        info!("game over: {}", outcome);
+        game.state = GameState::Finished;
+        let color = match outcome.winner() {
+            Some(PlayerColor::Red) => Color::rgb(0.85, 0.1, 0.1),
+            _ => Color::rgb(0.1, 0.1, 0.1),
+        };
+        commands.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            GameOverText,
+        ))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.9, 0.9, 0.9, 0.8).into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    outcome.to_string(),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color,
+                    },
+                ));
+            });
+        });
+    }
+}
+
+// ctrl+s saves the game as PGN and XQF, ctrl+o loads the saved PGN
+fn record_system(
+    keycode: Res<Input<KeyCode>>,
+    mut game: ResMut<Game>,
+    mut chess: ResMut<Chess>,
+    mut computer: Computer,
+    mut clock: ResMut<ChessClock>,
+    mut review: ResMut<Review>,
+    mut dragging: ResMut<Dragging>,
+) {
+    if !keycode.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keycode.just_pressed(KeyCode::S) {
+        let record = GameRecord::from_game(&chess);
+        for ext in ["pgn", "xqf"] {
+            let path = Path::new(SAVE_PATH).with_extension(ext);
+            match record.save(&path) {
+                Ok(()) => info!("game saved to {}", path.display()),
+                Err(err) => error!("cannot save {}: {}", path.display(), err),
+            }
+        }
+    } else if keycode.just_pressed(KeyCode::O) {
+        if game.state == GameState::Replaying {
+            // the game put aside for the replay would be lost
+            info!("end the replay before loading a game");
+            return;
+        }
+        match GameRecord::load(Path::new(SAVE_PATH)).and_then(|record| record.to_game()) {
+            Ok(loaded) => {
+                chess.game = with_rules(loaded);
+                clock.reset();
+                review.0 = None;
+                dragging.0 = None;
+                computer.stop();
+                game.to_play = None;
+                game.state = GameState::Starting;
+            }
+            Err(err) => error!("cannot load {}: {}", SAVE_PATH, err),
+        }
+    }
+}
+
+// the record replayed: the one given as the first argument, else the saved game
+fn record_path() -> PathBuf {
+    match std::env::args().nth(1) {
+        Some(arg) if arg.ends_with(".pgn") || arg.ends_with(".xqf") => PathBuf::from(arg),
+        _ => PathBuf::from(SAVE_PATH),
+    }
+}
+
+// the first argument may be a FEN or a .pgn/.xqf game record to continue
+fn initial_game() -> ChessGame {
+    with_rules(loaded_game())
+}
+
+// the draw and repetition rules from the config
+fn with_rules(mut game: ChessGame) -> ChessGame {
+    game.ruleset = RULESET;
+    game.move_limit = MOVE_LIMIT;
+    game
+}
+
+fn loaded_game() -> ChessGame {
+    let Some(arg) = std::env::args().nth(1) else {
+        return ChessGame::new();
+    };
+    let loaded = if arg.ends_with(".pgn") || arg.ends_with(".xqf") {
+        GameRecord::load(Path::new(&arg))
+            .and_then(|record| record.to_game())
+            .map_err(|err| err.to_string())
+    } else {
+        ChessGame::from_fen(&arg).map_err(|err| err.to_string())
+    };
+    loaded.unwrap_or_else(|err| {
+        error!("cannot load '{}': {}", arg, err);
+        ChessGame::new()
+    })
 }
 
 pub struct ChineseChess;
@@ -354,10 +1640,22 @@ impl Plugin for ChineseChess {
         // add things to your app here
         app.insert_resource(PosEntityMap(HashMap::<Pos, Entity>::new()))
             .insert_resource(SelectedSquare(None))
-            .insert_resource(Game::default())
-            .insert_resource(ChessGame::new())
+            .insert_resource(Dragging::default())
+            .insert_resource(KeyCursor::default())
+            .insert_resource(MoveEntry::default())
+            .insert_resource(Review::default())
+            .insert_resource(Replay::default())
+            .add_event::<UiCommand>()
+            .insert_resource(Game { ai: AI_COLOR, ..default() })
+            .insert_resource(AiTask::default())
+            .insert_resource(ExternalPlayer::launch())
+            .insert_resource(ChessClock::new())
+            // the human playing black sees the board from black's side
+            .insert_resource(Chess { game: initial_game(), flipped: AI_COLOR == Some(PlayerColor::Red) })
             .add_systems(Startup, setup)
-            .add_systems(Update, (place_pieces, mouse_click_system, display_moves, button_system))
-            .add_systems(Update, (play_move, move_to, die));
+            .add_systems(Update, (place_pieces, mouse_click_system, display_moves, highlight_system, button_system, command_system, draw_answer_system))
+            .add_systems(Update, (keyboard_system, key_cursor_system, move_entry_system, ui_scale_system, move_list_system, move_list_click_system))
+            .add_systems(Update, (button_bar_system, replay_system))
+            .add_systems(Update, (play_move, move_to, die, game_over_system, ai_system, record_system, clock_system, orientation_system));
     }
 }
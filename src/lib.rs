@@ -0,0 +1,13 @@
+// The xiangqi rules, free of any rendering, so they can be embedded in servers and tools.
+mod attacks;
+pub mod chess;
+pub mod clock;
+pub mod engine;
+pub mod external;
+pub mod fen;
+pub mod notation;
+pub mod pieces;
+pub mod pos;
+pub mod record;
+pub mod repetition;
+mod zobrist;
@@ -1,13 +1,10 @@
 mod configs;
-mod pieces;
 mod util;
 mod game;
-mod pos;
-mod chess;
 use configs::*;
-use game::{ChineseChess, ChessBoardTexture};
+use game::ChineseChess;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, window::WindowResizeConstraints};
 
 
 fn main() {
@@ -17,33 +14,18 @@ fn main() {
                 primary_window: Some(Window{
                     resolution: (WW as f32, WH as f32).into(),
                     title: "chineses-chess".to_string(),
+                    // the board and the buttons shrink with the window, down to this
+                    resize_constraints: WindowResizeConstraints {
+                        min_width: WW as f32 / 2.,
+                        min_height: WH as f32 / 2.,
+                        ..default()
+                    },
                     ..default()
                 }),
                 ..default()
             })
         )
         .add_plugins(ChineseChess)
-        // .add_systems(Update, update_board_size)
         .run();
 }
 
-
-// for debug adjusting the board picture size
-fn update_board_size(
-    mut query: Query<&mut Transform, With<ChessBoardTexture>>,
-    keycode: Res<Input<KeyCode>>,
-) {
-    let mut chessboard_transform = query.single_mut();
-    // todo
-    if keycode.pressed(KeyCode::Left) {
-        chessboard_transform.scale.x -= 0.01;
-    } else if keycode.pressed(KeyCode::Right) {
-        chessboard_transform.scale.x += 0.01;
-    } else if keycode.pressed(KeyCode::Up) {
-        chessboard_transform.scale.y += 0.01;
-    } else if keycode.pressed(KeyCode::Down) {
-        chessboard_transform.scale.y -= 0.01;
-    } else if keycode.pressed(KeyCode::P) {
-        println!("chessboard scale:{}", chessboard_transform.scale);
-    }
-}
\ No newline at end of file
@@ -0,0 +1,373 @@
+// Move notations used by other xiangqi tools:
+// - ICCS coordinates, files a-i from red's left and ranks 0-9 from red's side: h2e2
+// - WXF: piece letter, file, direction and destination: C2=5
+// - traditional chinese: 炮二平五
+// Files in WXF and chinese notation are counted from each player's own right hand,
+// red writes them with chinese numerals and black with (full width) digits.
+// When pieces of the same kind stand on one file, the front and rear ones are
+// told apart with +/- (前/后) in place of the file, or by their order counted
+// from the front when there are three or more of them (1/2/3, 前/中/后, 一二三四五).
+// When such pieces stand together on more than one file, the file follows the
+// piece to say which of them is meant (+P7+1, 前兵七进一).
+// Shi and xiang keep their file, the direction tells the two on it apart (仕六进五, 仕六退五).
+use crate::{
+    chess::Board,
+    pieces::{Action, Piece, PlayerColor},
+    pos::Pos,
+};
+
+const CHINESE_NUMBERS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+const FULL_WIDTH_NUMBERS: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+
+pub fn to_iccs(from: Pos, action: Action) -> String {
+    let to = action.target();
+    format!(
+        "{}{}{}{}",
+        (b'a' + from.0 as u8) as char,
+        from.1,
+        (b'a' + to.0 as u8) as char,
+        to.1
+    )
+}
+
+fn parse_iccs_square(file: char, rank: char) -> Option<Pos> {
+    let file = file.to_ascii_lowercase();
+    if !('a'..='i').contains(&file) {
+        return None;
+    }
+    let rank = rank.to_digit(10)?;
+    Some(Pos((file as u8 - b'a') as i32, rank as i32))
+}
+
+// accepts h2e2, H2E2 and h2-e2, the move has to be legal on `board`
+pub fn from_iccs(board: &Board, iccs: &str) -> Option<(Pos, Action)> {
+    let chars: Vec<char> = iccs.trim().chars().filter(|c| *c != '-').collect();
+    if chars.len() != 4 {
+        return None;
+    }
+    let from = parse_iccs_square(chars[0], chars[1])?;
+    let to = parse_iccs_square(chars[2], chars[3])?;
+    let (color, _) = (*board.get(from)?)?;
+    board
+        .legal_moves(color)
+        .into_iter()
+        .find(|(f, action)| *f == from && action.target() == to)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
+    Sideways,
+}
+
+// a move broken down the way both WXF and chinese notation describe it
+struct Description {
+    color: PlayerColor,
+    piece: Piece,
+    file: i32,
+    // (index counted from the front, count) when several such pieces share the file
+    tandem: Option<(usize, usize)>,
+    // another file holds a tandem of the same pieces too
+    tandem_files: bool,
+    direction: Direction,
+    dest: i32,
+}
+
+// files counted from the right hand of the player, 1 to 9
+fn file_number(x: i32, color: PlayerColor) -> i32 {
+    match color {
+        PlayerColor::Red => 9 - x,
+        PlayerColor::Black => x + 1,
+    }
+}
+
+fn describe(board: &Board, from: Pos, action: Action) -> Option<Description> {
+    let (color, piece) = (*board.get(from)?)?;
+    let to = action.target();
+    let forward = if color == PlayerColor::Red { 1 } else { -1 };
+    let dy = (to.1 - from.1) * forward;
+    let direction = if dy > 0 {
+        Direction::Forward
+    } else if dy < 0 {
+        Direction::Backward
+    } else {
+        Direction::Sideways
+    };
+    // pieces moving diagonally name the file they land on, the others count steps
+    let dest = match piece {
+        Piece::Shi | Piece::Xiang | Piece::Ma => file_number(to.0, color),
+        _ if direction == Direction::Sideways => file_number(to.0, color),
+        _ => dy.abs(),
+    };
+    // same pieces on a file
+    let same_on = |x: i32| -> Vec<i32> {
+        (0..10)
+            .filter(|y| {
+                matches!(board.get(Pos(x, *y)), Some(Some((c, p)))
+                    if *c == color && *p == piece)
+            })
+            .collect()
+    };
+    // front first
+    let mut ys = same_on(from.0);
+    ys.sort_by_key(|y| -y * forward);
+    let tandem = if ys.len() > 1 && !matches!(piece, Piece::Shi | Piece::Xiang) {
+        ys.iter().position(|y| *y == from.1).map(|i| (i, ys.len()))
+    } else {
+        None
+    };
+    let tandem_files = tandem.is_some() && (0..9).any(|x| x != from.0 && same_on(x).len() > 1);
+    Some(Description {
+        color,
+        piece,
+        file: file_number(from.0, color),
+        tandem,
+        tandem_files,
+        direction,
+        dest,
+    })
+}
+
+fn wxf_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Jiang => 'K',
+        Piece::Shi => 'A',
+        Piece::Xiang => 'E',
+        Piece::Ma => 'H',
+        Piece::Che => 'R',
+        Piece::Pao => 'C',
+        Piece::Bing => 'P',
+    }
+}
+
+// `board` is the position before the move
+pub fn to_wxf(board: &Board, from: Pos, action: Action) -> Option<String> {
+    let d = describe(board, from, action)?;
+    let direction = match d.direction {
+        Direction::Forward => '+',
+        Direction::Backward => '-',
+        Direction::Sideways => '=',
+    };
+    let letter = wxf_letter(d.piece);
+    let file = if d.tandem_files { d.file.to_string() } else { String::new() };
+    Some(match d.tandem {
+        None => format!("{}{}{}{}", letter, d.file, direction, d.dest),
+        Some((i, 2)) => format!("{}{}{}{}{}", if i == 0 { '+' } else { '-' }, letter, file, direction, d.dest),
+        Some((i, _)) => format!("{}{}{}{}{}", i + 1, letter, file, direction, d.dest),
+    })
+}
+
+fn normalize_wxf(wxf: &str) -> String {
+    let mut chars: Vec<char> = wxf
+        .trim()
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            '.' => '=',
+            'B' => 'E',
+            'N' => 'H',
+            c => c,
+        })
+        .collect();
+    // C+=5 is the same as +C=5
+    if chars.len() == 4 && chars[0].is_ascii_alphabetic() && (chars[1] == '+' || chars[1] == '-') {
+        chars.swap(0, 1);
+    }
+    chars.into_iter().collect()
+}
+
+pub fn from_wxf(board: &Board, color: PlayerColor, wxf: &str) -> Option<(Pos, Action)> {
+    let wxf = normalize_wxf(wxf);
+    board
+        .legal_moves(color)
+        .into_iter()
+        .find(|(from, action)| to_wxf(board, *from, *action).as_deref() == Some(wxf.as_str()))
+}
+
+fn chinese_name(color: PlayerColor, piece: Piece) -> char {
+    match (color, piece) {
+        (PlayerColor::Red, Piece::Jiang) => '帅',
+        (PlayerColor::Black, Piece::Jiang) => '将',
+        (PlayerColor::Red, Piece::Shi) => '仕',
+        (PlayerColor::Black, Piece::Shi) => '士',
+        (PlayerColor::Red, Piece::Xiang) => '相',
+        (PlayerColor::Black, Piece::Xiang) => '象',
+        (_, Piece::Ma) => '马',
+        (_, Piece::Che) => '车',
+        (_, Piece::Pao) => '炮',
+        (PlayerColor::Red, Piece::Bing) => '兵',
+        (PlayerColor::Black, Piece::Bing) => '卒',
+    }
+}
+
+fn chinese_number(color: PlayerColor, n: i32) -> char {
+    match color {
+        PlayerColor::Red => CHINESE_NUMBERS[n as usize - 1],
+        PlayerColor::Black => FULL_WIDTH_NUMBERS[n as usize - 1],
+    }
+}
+
+// `board` is the position before the move
+pub fn to_chinese(board: &Board, from: Pos, action: Action) -> Option<String> {
+    let d = describe(board, from, action)?;
+    let direction = match d.direction {
+        Direction::Forward => '进',
+        Direction::Backward => '退',
+        Direction::Sideways => '平',
+    };
+    let name = chinese_name(d.color, d.piece);
+    let dest = chinese_number(d.color, d.dest);
+    Some(match d.tandem {
+        None => format!("{}{}{}{}", name, chinese_number(d.color, d.file), direction, dest),
+        Some((i, n)) => {
+            let order = match (i, n) {
+                (0, 2) | (0, 3) => '前',
+                (1, 2) | (2, 3) => '后',
+                (1, 3) => '中',
+                _ => CHINESE_NUMBERS[i],
+            };
+            let file = if d.tandem_files { chinese_number(d.color, d.file).to_string() } else { String::new() };
+            format!("{}{}{}{}{}", order, name, file, direction, dest)
+        }
+    })
+}
+
+// fold traditional characters, piece names of either side and all numerals
+// onto one spelling so that any common way of writing a move compares equal
+fn normalize_chinese(chinese: &str) -> String {
+    chinese
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            if let Some(i) = CHINESE_NUMBERS.iter().position(|n| *n == c) {
+                return char::from_digit(i as u32 + 1, 10).unwrap();
+            }
+            if let Some(i) = FULL_WIDTH_NUMBERS.iter().position(|n| *n == c) {
+                return char::from_digit(i as u32 + 1, 10).unwrap();
+            }
+            match c {
+                '帅' | '帥' | '將' => '将',
+                '仕' => '士',
+                '相' => '象',
+                '馬' | '傌' | '瑪' => '马',
+                '車' | '俥' | '硨' => '车',
+                '砲' | '包' => '炮',
+                '卒' => '兵',
+                '進' => '进',
+                '後' => '后',
+                c => c,
+            }
+        })
+        .collect()
+}
+
+pub fn from_chinese(board: &Board, color: PlayerColor, chinese: &str) -> Option<(Pos, Action)> {
+    let chinese = normalize_chinese(chinese);
+    board.legal_moves(color).into_iter().find(|(from, action)| {
+        to_chinese(board, *from, *action).map(|s| normalize_chinese(&s)).as_deref() == Some(chinese.as_str())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::START_FEN;
+
+    // (wxf, chinese) of the move given in ICCS
+    fn names(fen: &str, iccs: &str) -> (String, String) {
+        let board = Board::from_fen(fen).unwrap();
+        let (from, action) = from_iccs(&board, iccs).unwrap();
+        (to_wxf(&board, from, action).unwrap(), to_chinese(&board, from, action).unwrap())
+    }
+
+    fn iccs(found: Option<(Pos, Action)>) -> Option<String> {
+        found.map(|(from, action)| to_iccs(from, action))
+    }
+
+    // every legal move reads back as itself
+    fn assert_round_trips(fen: &str) {
+        let board = Board::from_fen(fen).unwrap();
+        for color in [PlayerColor::Red, PlayerColor::Black] {
+            for (from, action) in board.legal_moves(color) {
+                let wxf = to_wxf(&board, from, action).unwrap();
+                let chinese = to_chinese(&board, from, action).unwrap();
+                let expected = Some(to_iccs(from, action));
+                assert_eq!(iccs(from_wxf(&board, color, &wxf)), expected, "{} {}", fen, wxf);
+                assert_eq!(iccs(from_chinese(&board, color, &chinese)), expected, "{} {}", fen, chinese);
+            }
+        }
+    }
+
+    #[test]
+    fn black_counts_files_with_full_width_digits() {
+        assert_eq!(names(START_FEN, "h2e2"), ("C2=5".to_string(), "炮二平五".to_string()));
+        assert_eq!(names(START_FEN, "b0c2"), ("H8+7".to_string(), "马八进七".to_string()));
+        assert_eq!(names(START_FEN, "h7e7"), ("C8=5".to_string(), "炮８平５".to_string()));
+        assert_eq!(names(START_FEN, "b9c7"), ("H2+3".to_string(), "马２进３".to_string()));
+    }
+
+    #[test]
+    fn tandem_pieces_are_told_apart() {
+        // two red che on the a file
+        let fen = "3k5/9/9/9/9/9/R8/9/9/R3K4 w";
+        assert_eq!(names(fen, "a3a4"), ("+R+1".to_string(), "前车进一".to_string()));
+        assert_eq!(names(fen, "a0b0"), ("-R=8".to_string(), "后车平八".to_string()));
+        // three red bing on the c file
+        let fen = "3k5/9/2P6/2P6/2P6/9/9/9/9/4K4 w";
+        assert_eq!(names(fen, "c7b7"), ("1P=8".to_string(), "前兵平八".to_string()));
+        assert_eq!(names(fen, "c6d6"), ("2P=6".to_string(), "中兵平六".to_string()));
+        assert_eq!(names(fen, "c5b5"), ("3P=8".to_string(), "后兵平八".to_string()));
+        assert_round_trips(fen);
+    }
+
+    #[test]
+    fn tandems_on_two_files_name_the_file() {
+        // two red bing on the c file and two on the e file
+        let fen = "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w";
+        assert_eq!(names(fen, "e6e7"), ("+P5+1".to_string(), "前兵五进一".to_string()));
+        assert_eq!(names(fen, "c6c7"), ("+P7+1".to_string(), "前兵七进一".to_string()));
+        assert_eq!(names(fen, "e6d6"), ("+P5=6".to_string(), "前兵五平六".to_string()));
+        assert_eq!(names(fen, "c5d5"), ("-P7=6".to_string(), "后兵七平六".to_string()));
+        assert_round_trips(fen);
+        // and two files of black ma
+        let fen = "4k4/9/2n1n4/9/2n1n4/9/9/9/9/4K4 b";
+        assert_eq!(names(fen, "c7b5"), ("-H3+2".to_string(), "后马３进２".to_string()));
+        assert_eq!(names(fen, "e5d3"), ("+H5+4".to_string(), "前马５进４".to_string()));
+        assert_round_trips(fen);
+    }
+
+    #[test]
+    fn shi_and_xiang_keep_their_file() {
+        let fen = "3k5/9/9/9/9/2B6/9/3A5/9/2BAK4 w";
+        assert_eq!(names(fen, "d0e1"), ("A6+5".to_string(), "仕六进五".to_string()));
+        assert_eq!(names(fen, "d2e1"), ("A6-5".to_string(), "仕六退五".to_string()));
+        assert_eq!(names(fen, "c0e2"), ("E7+5".to_string(), "相七进五".to_string()));
+        assert_eq!(names(fen, "c4e2"), ("E7-5".to_string(), "相七退五".to_string()));
+        assert_round_trips(fen);
+    }
+
+    #[test]
+    fn every_move_reads_back() {
+        assert_round_trips(START_FEN);
+        assert_round_trips("r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w");
+    }
+
+    #[test]
+    fn other_spellings_are_understood() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let h2e2 = Some("h2e2".to_string());
+        assert_eq!(iccs(from_iccs(&board, "H2-E2")), h2e2);
+        assert_eq!(iccs(from_wxf(&board, PlayerColor::Red, "c2.5")), h2e2);
+        assert_eq!(iccs(from_chinese(&board, PlayerColor::Red, "砲二平五")), h2e2);
+        assert_eq!(iccs(from_chinese(&board, PlayerColor::Red, "炮2平5")), h2e2);
+        assert_eq!(iccs(from_wxf(&board, PlayerColor::Red, "n8+7")), Some("b0c2".to_string()));
+        assert_eq!(iccs(from_chinese(&board, PlayerColor::Black, "馬2進3")), Some("b9c7".to_string()));
+        // front and rear written after the letter, traditional characters
+        let board = Board::from_fen("3k5/9/9/9/9/9/R8/9/9/R3K4 w").unwrap();
+        assert_eq!(iccs(from_wxf(&board, PlayerColor::Red, "R-.8")), Some("a0b0".to_string()));
+        assert_eq!(iccs(from_chinese(&board, PlayerColor::Red, "後俥平八")), Some("a0b0".to_string()));
+        // a tandem has to be named by its order
+        assert_eq!(iccs(from_wxf(&board, PlayerColor::Red, "R9=8")), None);
+    }
+}
@@ -1,6 +1,10 @@
-use crate::{pos::Pos, chess::Board};
+use crate::{
+    attacks::{point, pos, side, tables, DOWN, UP},
+    chess::Board,
+    pos::Pos,
+};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum PlayerColor {
     Black,
     Red,
@@ -15,13 +19,32 @@ impl PlayerColor {
     }
 }
 
+impl std::fmt::Display for PlayerColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlayerColor::Black => write!(f, "black"),
+            PlayerColor::Red => write!(f, "red"),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum Action {
     Go(Pos),
     Take(Pos),
 }
 
-#[derive(Clone, Copy)]
+impl Action {
+    // the point the piece ends up on
+    pub fn target(self) -> Pos {
+        match self {
+            Action::Go(to) => to,
+            Action::Take(to) => to,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Piece {
     Jiang,
     Shi,
@@ -34,14 +57,22 @@ pub enum Piece {
 
 impl Piece {
     pub fn moves(self, board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
+        let mut actions = vec![];
+        self.push_moves(board, from, color, &mut actions);
+        actions
+    }
+
+    // like `moves`, appending to a list the caller reuses
+    pub fn push_moves(self, board: &Board, from: Pos, color: PlayerColor, actions: &mut Vec<Action>) {
+        let from = point(from);
         match self {
-            Self::Jiang => jiang_moves(board, from, color),
-            Self::Shi => shi_moves(board, from, color),
-            Self::Xiang => xiang_moves(board, from, color),
-            Self::Ma => ma_moves(board, from, color),
-            Self::Che => che_moves(board, from, color),
-            Self::Pao => pao_moves(board, from, color),
-            Self::Bing => bing_moves(board, from, color),
+            Self::Jiang => jiang_moves(board, from, color, actions),
+            Self::Shi => step_moves(board, &tables().shi[side(color)][from], color, actions),
+            Self::Xiang => xiang_moves(board, from, color, actions),
+            Self::Ma => ma_moves(board, from, color, actions),
+            Self::Che => che_moves(board, from, color, actions),
+            Self::Pao => pao_moves(board, from, color, actions),
+            Self::Bing => step_moves(board, &tables().bing[side(color)][from], color, actions),
         }
     }
 }
@@ -60,197 +91,183 @@ impl std::fmt::Display for Piece {
     }
 }
 
-fn jiang_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![Pos(-1, 0), Pos(1, 0), Pos(0, 1), Pos(0, -1)];
-    let mut bound = vec![Pos(3, 0), Pos(5, 2)];
-    if color == PlayerColor::Black {
-        bound[0].1 += 7;
-        bound[1].1 += 7;
-    }
-    let mut actions = vec![];
-    for dir in dirs {
-        let to = from + dir;
-        if to.in_bound(bound[0], bound[1]) {
-            if let Some(grid) = board.get(to) {
-                if let Some((c, _)) = grid {
-                    if *c != color {
-                        actions.push(Action::Take(to));
-                    }
-                } else {
-                    actions.push(Action::Go(to));
-                }
+// go to an empty point or take an enemy piece
+fn step(board: &Board, to: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    match board.at(to) {
+        Some((c, _)) => {
+            if c != color {
+                actions.push(Action::Take(pos(to)));
             }
         }
+        None => actions.push(Action::Go(pos(to))),
     }
-    actions
 }
 
-fn shi_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![Pos(-1, -1), Pos(1, 1), Pos(-1, 1), Pos(1, -1)];
-    let mut bound = vec![Pos(3, 0), Pos(5, 2)];
-    if color == PlayerColor::Black {
-        bound[0].1 += 7;
-        bound[1].1 += 7;
+fn step_moves(board: &Board, targets: &[usize], color: PlayerColor, actions: &mut Vec<Action>) {
+    for to in targets {
+        step(board, *to, color, actions);
     }
-    let mut actions = vec![];
-    for dir in dirs {
-        let to = from + dir;
-        if to.in_bound(bound[0], bound[1]) {
-            if let Some(grid) = board.get(to) {
-                if let Some((c, _)) = grid {
-                    if *c != color {
-                        actions.push(Action::Take(to));
-                    }
-                } else {
-                    actions.push(Action::Go(to));
-                }
+}
+
+fn jiang_moves(board: &Board, from: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    step_moves(board, &tables().jiang[side(color)][from], color, actions);
+    // flying general: the two jiang may not face each other on an open file
+    let forward = if color == PlayerColor::Red { UP } else { DOWN };
+    for to in tables().rays[from][forward].iter() {
+        if let Some((c, p)) = board.at(*to) {
+            if c != color && p == Piece::Jiang {
+                actions.push(Action::Take(pos(*to)));
             }
+            break;
         }
     }
-    actions
 }
 
-fn xiang_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![Pos(-2, -2), Pos(2, 2), Pos(-2, 2), Pos(2, -2)];
-    let mut bound = vec![Pos(0, 0), Pos(8, 4)];
-    if color == PlayerColor::Black {
-        bound[0].1 += 5;
-        bound[1].1 += 5;
-    }
-    let mut actions = vec![];
-    for dir in dirs {
-        let to = from + dir;
-        if to.in_bound(bound[0], bound[1]) {
-            if let Some(Some((_, _))) = board.get(from + Pos(dir.0 / 2, dir.1 / 2)) {
-                continue; // blocked
-            }
-            if let Some(grid) = board.get(to) {
-                if let Some((c, _)) = grid {
-                    if *c != color {
-                        actions.push(Action::Take(to));
-                    }
-                } else {
-                    actions.push(Action::Go(to));
-                }
-            }
+fn xiang_moves(board: &Board, from: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    for (to, eye) in tables().xiang[side(color)][from].iter() {
+        if board.at(*eye).is_none() {
+            step(board, *to, color, actions);
         }
     }
-    actions
 }
 
-fn ma_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![
-        Pos(-1, -2),
-        Pos(1, -2),
-        Pos(-2, -1),
-        Pos(2, -1),
-        Pos(-1, 2),
-        Pos(1, 2),
-        Pos(-2, 1),
-        Pos(2, 1),
-    ];
-    let mut actions = vec![];
-    for dir in dirs {
-        let to = from + dir;
-        if let Some(grid) = board.get(to) {
-            let mut blocked = false;
-            if dir.0 == 2 || dir.0 == -2 {
-                blocked = board.get(from + Pos(dir.0 / 2, 0)).unwrap().is_some();
-            } else {
-                blocked = board.get(from + Pos(0, dir.1 / 2)).unwrap().is_some();
-            }
-            if !blocked {
-                if let Some((c, _)) = grid {
-                    if *c != color {
-                        actions.push(Action::Take(to));
-                    }
-                } else {
-                    actions.push(Action::Go(to));
-                }
-            }
+fn ma_moves(board: &Board, from: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    for (to, leg) in tables().ma[from].iter() {
+        if board.at(*leg).is_none() {
+            step(board, *to, color, actions);
         }
     }
-    actions
 }
 
-fn che_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![Pos(-1, 0), Pos(1, 0), Pos(0, 1), Pos(0, -1)];
-    let mut actions = vec![];
-    for dir in dirs {
-        let mut to = from + dir;
-        while to.in_bound(Pos(0, 0), Pos(8, 9)) {
-            if let Some(grid) = board.get(to) {
-                if let Some((c, _)) = grid {
-                    if *c != color {
-                        actions.push(Action::Take(to));
-                    }
-                    break;
-                } else {
-                    actions.push(Action::Go(to));
-                }
+fn che_moves(board: &Board, from: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    for ray in tables().rays[from].iter() {
+        for to in ray {
+            step(board, *to, color, actions);
+            if board.at(*to).is_some() {
+                break;
             }
-            to = to + dir;
         }
     }
-    actions
 }
 
-#[derive(PartialEq)]
-enum PaoState {
-    Jumped,
-    Driving,
-}
-
-fn pao_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let dirs = vec![Pos(-1, 0), Pos(1, 0), Pos(0, 1), Pos(0, -1)];
-    let mut actions = vec![];
-    for dir in dirs {
-        let mut to = from + dir;
-        let mut state = PaoState::Driving;
-        while let Some(grid) = board.get(to) {
-            if let Some((c, _)) = grid {
-                if state == PaoState::Jumped {
-                    if *c != color {
-                        actions.push(Action::Take(to));
+fn pao_moves(board: &Board, from: usize, color: PlayerColor, actions: &mut Vec<Action>) {
+    for ray in tables().rays[from].iter() {
+        let mut jumped = false;
+        for to in ray {
+            match board.at(*to) {
+                None => {
+                    if !jumped {
+                        actions.push(Action::Go(pos(*to)));
                     }
-                    break;
-                } else {
-                    state = PaoState::Jumped;
                 }
-            } else {
-                if state == PaoState::Driving {
-                    actions.push(Action::Go(to));
+                Some((c, _)) => {
+                    if jumped {
+                        if c != color {
+                            actions.push(Action::Take(pos(*to)));
+                        }
+                        break;
+                    }
+                    jumped = true;
                 }
             }
-            to = to + dir;
         }
     }
-    actions
 }
 
-fn bing_moves(board: &Board, from: Pos, color: PlayerColor) -> Vec<Action> {
-    let mut dirs = vec![Pos(0, 1), Pos(-1, 0), Pos(1, 0)];
-    let mut bound = vec![Pos(0, 0), Pos(8, 4)];
-    if color == PlayerColor::Black {
-        dirs[0].1 = -1;
-        bound[0].1 += 5;
-        bound[1].1 += 5;
-    }
-    let mut actions = vec![];
-    for dir in dirs {
-        let to = from + dir;
-        if let Some(grid) = board.get(to) {
-            if let Some((c, _)) = grid {
-                if *c != color {
-                    actions.push(Action::Take(to));
-                }
-            } else {
-                actions.push(Action::Go(to));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (takes, goes) reachable by the piece on `from`, sorted
+    fn targets(fen: &str, from: Pos) -> (Vec<Pos>, Vec<Pos>) {
+        let board = Board::from_fen(fen).unwrap();
+        let (color, piece) = board.get(from).unwrap().unwrap();
+        let mut takes = vec![];
+        let mut goes = vec![];
+        for action in piece.moves(&board, from, color) {
+            match action {
+                Action::Take(to) => takes.push(to),
+                Action::Go(to) => goes.push(to),
             }
         }
-        if from.in_bound(bound[0], bound[1]) {
-            break; // in self bound
-        }
+        takes.sort_by_key(|p| (p.0, p.1));
+        goes.sort_by_key(|p| (p.0, p.1));
+        (takes, goes)
+    }
+
+    #[test]
+    fn pao_needs_a_screen_to_take() {
+        // red pao on e0, a red screen on e2, black pieces on e5 and e7
+        let (takes, goes) = targets("4k4/9/4p4/9/4p4/9/9/4P4/9/4C4 w", Pos(4, 0));
+        assert_eq!(takes, vec![Pos(4, 5)]);
+        assert!(goes.contains(&Pos(4, 1)));
+        assert!(!goes.contains(&Pos(4, 3)));
+        assert!(goes.contains(&Pos(0, 0)) && goes.contains(&Pos(8, 0)));
+    }
+
+    #[test]
+    fn pao_does_not_take_without_screen() {
+        let (takes, goes) = targets("4k4/9/9/9/9/9/9/9/9/c3C4 w", Pos(4, 0));
+        assert!(takes.is_empty());
+        assert!(!goes.contains(&Pos(0, 0)));
+        assert!(goes.contains(&Pos(1, 0)));
+    }
+
+    #[test]
+    fn ma_leg_blocking() {
+        let fen = "4k4/9/9/9/9/4P4/4N4/9/9/4K4 w";
+        let (takes, goes) = targets(fen, Pos(4, 3));
+        assert!(takes.is_empty());
+        // the pawn on e4 blocks both jumps towards the top
+        assert!(!goes.contains(&Pos(3, 5)) && !goes.contains(&Pos(5, 5)));
+        assert!(goes.contains(&Pos(2, 4)) && goes.contains(&Pos(6, 4)));
+        assert!(goes.contains(&Pos(3, 1)) && goes.contains(&Pos(5, 1)));
+        assert_eq!(goes.len(), 6);
+    }
+
+    #[test]
+    fn xiang_eye_blocking_and_river() {
+        // red xiang on e4 at the river bank, the eye towards c2 is blocked
+        let fen = "4k4/9/9/9/9/4B4/3P5/9/9/4K4 w";
+        let (_, goes) = targets(fen, Pos(4, 4));
+        assert_eq!(goes, vec![Pos(6, 2)]);
+        // black xiang may not cross to red's side either
+        let (_, goes) = targets("4k4/9/9/9/4b4/9/9/9/9/4K4 b", Pos(4, 5));
+        assert_eq!(goes, vec![Pos(2, 7), Pos(6, 7)]);
+    }
+
+    #[test]
+    fn bing_moves_sideways_after_the_river() {
+        let (_, goes) = targets("4k4/9/9/9/9/9/4P4/9/9/4K4 w", Pos(4, 3));
+        assert_eq!(goes, vec![Pos(4, 4)]);
+        let (_, goes) = targets("4k4/9/9/9/4P4/9/9/9/9/4K4 w", Pos(4, 5));
+        assert_eq!(goes, vec![Pos(3, 5), Pos(4, 6), Pos(5, 5)]);
+        // black goes down the board and never backwards
+        let (_, goes) = targets("4k4/9/9/9/9/4p4/9/9/9/4K4 b", Pos(4, 4));
+        assert_eq!(goes, vec![Pos(3, 4), Pos(4, 3), Pos(5, 4)]);
     }
-    actions
-}
\ No newline at end of file
+
+    #[test]
+    fn jiang_stays_in_palace() {
+        let (_, goes) = targets("4k4/9/9/9/9/9/9/9/9/3K5 w", Pos(3, 0));
+        assert_eq!(goes, vec![Pos(3, 1), Pos(4, 0)]);
+        let (_, goes) = targets("9/9/5k3/9/9/9/9/9/9/3K5 b", Pos(5, 7));
+        assert_eq!(goes, vec![Pos(4, 7), Pos(5, 8)]);
+    }
+
+    #[test]
+    fn shi_stays_in_palace() {
+        let (_, goes) = targets("4k4/9/9/9/9/9/9/9/4A4/3K5 w", Pos(4, 1));
+        assert_eq!(goes, vec![Pos(3, 2), Pos(5, 0), Pos(5, 2)]);
+        let (_, goes) = targets("3ak4/9/9/9/9/9/9/9/9/5K3 b", Pos(3, 9));
+        assert_eq!(goes, vec![Pos(4, 8)]);
+    }
+
+    #[test]
+    fn jiang_takes_across_open_file() {
+        let (takes, _) = targets("4k4/9/9/9/9/9/9/9/9/4K4 w", Pos(4, 0));
+        assert_eq!(takes, vec![Pos(4, 9)]);
+        let (takes, _) = targets("4k4/9/9/9/4p4/9/9/9/9/4K4 w", Pos(4, 0));
+        assert!(takes.is_empty());
+    }
+}
@@ -1,5 +1,3 @@
-use bevy::prelude::*;
-use std::collections::HashMap;
 use std::ops::{Add, Mul};
 use std::fmt;
 
@@ -17,10 +15,7 @@ impl Add for Pos {
 
     fn add(self, rhs: Self) -> Self::Output {
         // Self(self.0 + rhs.0, self.1 + rhs.1)
-        Self {
-            0: self.0 + rhs.0,
-            1: self.1 + rhs.1,
-        }
+        Self(self.0 + rhs.0, self.1 + rhs.1)
     }
 }
 
@@ -38,6 +33,3 @@ impl fmt::Display for Pos {
     }
 }
 
-
-#[derive(Resource, Default)]
-pub struct PosEntityMap(pub HashMap<Pos, Entity>);
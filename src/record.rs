@@ -0,0 +1,541 @@
+// Whole game records, readable by other xiangqi software:
+// - PGN with the xiangqi tags (Red, Black, Result, FEN, Format) and ICCS move text
+// - XQF, the binary format of XQStudio, in its unencrypted form (version 10 and older)
+use crate::{
+    chess::{Board, ChessGame, DrawReason, Outcome, WinReason, START_FEN},
+    fen::FenError,
+    notation,
+    pieces::{Action, Piece, PlayerColor},
+    pos::Pos,
+};
+use std::{fmt, fs, path::Path};
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(std::io::Error),
+    Fen(FenError),
+    // the move text that could not be played, counted from 1
+    BadMove(usize, String),
+    NotXqf,
+    EncryptedXqf(u8),
+    Truncated,
+    UnknownFormat(String),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io(err) => write!(f, "{}", err),
+            RecordError::Fen(err) => write!(f, "bad FEN tag: {}", err),
+            RecordError::BadMove(i, mv) => write!(f, "move {} '{}' is not playable", i, mv),
+            RecordError::NotXqf => write!(f, "not a XQF file"),
+            RecordError::EncryptedXqf(version) => write!(f, "encrypted XQF version {} is not supported", version),
+            RecordError::Truncated => write!(f, "record is truncated"),
+            RecordError::UnknownFormat(format) => write!(f, "unknown record format '{}'", format),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<std::io::Error> for RecordError {
+    fn from(err: std::io::Error) -> Self {
+        RecordError::Io(err)
+    }
+}
+
+impl From<FenError> for RecordError {
+    fn from(err: FenError) -> Self {
+        RecordError::Fen(err)
+    }
+}
+
+pub struct GameRecord {
+    // PGN tag pairs in the order they are written
+    pub tags: Vec<(String, String)>,
+    // start position
+    pub fen: String,
+    pub moves: Vec<(Pos, Action)>,
+}
+
+fn result_tag(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Win(PlayerColor::Red, _)) => "1-0",
+        Some(Outcome::Win(PlayerColor::Black, _)) => "0-1",
+        Some(Outcome::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+impl GameRecord {
+    pub fn from_game(game: &ChessGame) -> GameRecord {
+        let mut record = GameRecord {
+            tags: vec![],
+            fen: game.start_fen.clone(),
+            moves: game.history.iter().map(|m| (m.from, m.action)).collect(),
+        };
+        for (name, value) in [
+            ("Game", "Chinese Chess"),
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Red", "?"),
+            ("Black", "?"),
+            ("Result", result_tag(game.outcome())),
+        ] {
+            record.set_tag(name, value);
+        }
+        record
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // replay the record from its start position
+    pub fn to_game(&self) -> Result<ChessGame, RecordError> {
+        let mut game = ChessGame::from_fen(&self.fen)?;
+        for (i, (from, action)) in self.moves.iter().enumerate() {
+            if game.playable_move(*from, action.target()).is_none() {
+                return Err(RecordError::BadMove(i + 1, notation::to_iccs(*from, *action)));
+            }
+            game.play(*from, *action);
+        }
+        // a result the moves do not account for was a resignation or an agreed draw
+        if game.outcome().is_none() {
+            game.result = match self.tag("Result") {
+                Some("1-0") => Some(Outcome::Win(PlayerColor::Red, WinReason::Resignation)),
+                Some("0-1") => Some(Outcome::Win(PlayerColor::Black, WinReason::Resignation)),
+                Some("1/2-1/2") => Some(Outcome::Draw(DrawReason::Agreement)),
+                _ => None,
+            };
+        }
+        Ok(game)
+    }
+
+    // picks the format from the file extension, .pgn or .xqf
+    pub fn load(path: &Path) -> Result<GameRecord, RecordError> {
+        match extension(path).as_str() {
+            "pgn" => GameRecord::from_pgn(&fs::read_to_string(path)?),
+            "xqf" => GameRecord::from_xqf(&fs::read(path)?),
+            other => Err(RecordError::UnknownFormat(other.to_string())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RecordError> {
+        match extension(path).as_str() {
+            "pgn" => fs::write(path, self.to_pgn())?,
+            "xqf" => fs::write(path, self.to_xqf())?,
+            other => return Err(RecordError::UnknownFormat(other.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in self.tags.iter() {
+            if name != "FEN" && name != "Format" {
+                pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+            }
+        }
+        if self.fen != START_FEN {
+            pgn.push_str(&format!("[FEN \"{}\"]\n", self.fen));
+        }
+        pgn.push_str("[Format \"ICCS\"]\n\n");
+
+        let black_first = self.fen.split_whitespace().nth(1) == Some("b");
+        let mut tokens = vec![];
+        for (i, (from, action)) in self.moves.iter().enumerate() {
+            let ply = i + black_first as usize;
+            if ply % 2 == 1 {
+                if i == 0 {
+                    tokens.push("1...".to_string());
+                }
+            } else {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            }
+            let iccs = notation::to_iccs(*from, *action).to_uppercase();
+            tokens.push(format!("{}-{}", &iccs[..2], &iccs[2..]));
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    // the main line of the first game in `pgn`, moves may be in ICCS, WXF or chinese notation
+    pub fn from_pgn(pgn: &str) -> Result<GameRecord, RecordError> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for line in pgn.lines() {
+            let line = line.trim();
+            if let Some(tag) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                // tags after the move text belong to the next game
+                if !movetext.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = tag.split_once(' ') {
+                    let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+                    tags.push((name.to_string(), value));
+                }
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+        let mut record = GameRecord { tags, fen: START_FEN.to_string(), moves: vec![] };
+        if let Some(fen) = record.tag("FEN") {
+            record.fen = fen.to_string();
+        }
+        let format = record.tag("Format").unwrap_or("ICCS").to_uppercase();
+
+        let mut game = ChessGame::from_fen(&record.fen)?;
+        for token in strip_comments(&movetext).split_whitespace() {
+            if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                continue;
+            }
+            // move numbers may stick to the move: 1.H2-E2
+            let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let token = match token[digits..].strip_prefix('.') {
+                Some(rest) => rest.trim_start_matches('.'),
+                None => token,
+            };
+            if token.is_empty() {
+                continue;
+            }
+            let color = game.player.unwrap_or(PlayerColor::Red);
+            let parsed = match format.as_str() {
+                "WXF" => notation::from_wxf(&game.board, color, token),
+                "CHINESE" => notation::from_chinese(&game.board, color, token),
+                _ => notation::from_iccs(&game.board, token),
+            };
+            match parsed {
+                Some((from, action)) if game.playable_move(from, action.target()).is_some() => {
+                    game.play(from, action);
+                    record.moves.push((from, action));
+                }
+                _ => return Err(RecordError::BadMove(record.moves.len() + 1, token.to_string())),
+            }
+        }
+        Ok(record)
+    }
+
+    pub fn to_xqf(&self) -> Vec<u8> {
+        let mut data = vec![0u8; XQF_HEADER_LEN];
+        data[0] = b'X';
+        data[1] = b'Q';
+        data[2] = XQF_VERSION;
+
+        // pieces go into the first free slot of their kind, unused slots are 0xff
+        let mut slots = [XQF_NONE; 32];
+        if let Ok(board) = Board::from_fen(&self.fen) {
+            for x in 0..9 {
+                for y in 0..10 {
+                    if let Some(Some((color, piece))) = board.get(Pos(x, y)) {
+                        let offset = if *color == PlayerColor::Red { 0 } else { 16 };
+                        if let Some(i) = (0..16).find(|i| XQF_SLOTS[*i] == *piece && slots[offset + i] == XQF_NONE) {
+                            slots[offset + i] = (x * 10 + y) as u8;
+                        }
+                    }
+                }
+            }
+        }
+        data[XQF_BOARD..XQF_BOARD + 32].copy_from_slice(&slots);
+        data[XQF_RESULT] = match self.tag("Result") {
+            Some("1-0") => 1,
+            Some("0-1") => 2,
+            Some("1/2-1/2") => 3,
+            _ => 0,
+        };
+        for (name, offset, len) in XQF_TEXT_FIELDS {
+            if let Some(value) = self.tag(name) {
+                write_xqf_text(&mut data[offset..offset + len + 1], value);
+            }
+        }
+
+        // the root record carries no move
+        let mut records = vec![(Pos(0, 0), Pos(0, 0))];
+        records.extend(self.moves.iter().map(|(from, action)| (*from, action.target())));
+        for (i, (from, to)) in records.iter().enumerate() {
+            let has_next = i + 1 < records.len();
+            data.push((from.0 * 10 + from.1) as u8 + XQF_FROM_OFFSET);
+            data.push((to.0 * 10 + to.1) as u8 + XQF_TO_OFFSET);
+            data.push(if has_next { 0xf0 } else { 0 });
+            data.push(0);
+            // no comment
+            data.extend_from_slice(&0u32.to_le_bytes());
+        }
+        data
+    }
+
+    // the main line of a XQF file, variations and comments are skipped
+    pub fn from_xqf(data: &[u8]) -> Result<GameRecord, RecordError> {
+        if data.len() < XQF_HEADER_LEN || &data[0..2] != b"XQ" {
+            return Err(RecordError::NotXqf);
+        }
+        if data[2] > XQF_VERSION {
+            return Err(RecordError::EncryptedXqf(data[2]));
+        }
+        let mut board = Board::new();
+        for (i, v) in data[XQF_BOARD..XQF_BOARD + 32].iter().enumerate() {
+            if *v < 90 {
+                let color = if i < 16 { PlayerColor::Red } else { PlayerColor::Black };
+                board.set(Pos(*v as i32 / 10, *v as i32 % 10), Some((color, XQF_SLOTS[i % 16])));
+            }
+        }
+
+        let mut tags = vec![("Game".to_string(), "Chinese Chess".to_string())];
+        for (name, offset, len) in XQF_TEXT_FIELDS {
+            let value = read_xqf_text(&data[offset..offset + len + 1]);
+            if !value.is_empty() {
+                tags.push((name.to_string(), value));
+            }
+        }
+        let result = match data[XQF_RESULT] {
+            1 => "1-0",
+            2 => "0-1",
+            3 => "1/2-1/2",
+            _ => "*",
+        };
+        tags.push(("Result".to_string(), result.to_string()));
+
+        let mut moves = vec![];
+        let mut offset = XQF_HEADER_LEN;
+        let mut has_next = true;
+        let mut root = true;
+        while has_next {
+            let record = data.get(offset..offset + 8).ok_or(RecordError::Truncated)?;
+            let comment_len = u32::from_le_bytes([record[4], record[5], record[6], record[7]]) as usize;
+            offset += 8 + comment_len;
+            has_next = record[2] & 0xf0 != 0;
+            if root {
+                root = false;
+                continue;
+            }
+            let from = record[0].wrapping_sub(XQF_FROM_OFFSET);
+            let to = record[1].wrapping_sub(XQF_TO_OFFSET);
+            if from >= 90 || to >= 90 {
+                return Err(RecordError::BadMove(moves.len() + 1, format!("{:02x}{:02x}", record[0], record[1])));
+            }
+            moves.push((Pos(from as i32 / 10, from as i32 % 10), Pos(to as i32 / 10, to as i32 % 10)));
+        }
+
+        // the side of the first moving piece starts
+        let side = match moves.first().and_then(|(from, _)| *board.get(*from).unwrap()) {
+            Some((PlayerColor::Black, _)) => "b",
+            _ => "w",
+        };
+        let fen = format!("{} {} - - 0 1", board.to_fen(), side);
+        let mut game = ChessGame::from_fen(&fen)?;
+        let mut record = GameRecord { tags, fen, moves: vec![] };
+        for (from, to) in moves {
+            match game.playable_move(from, to) {
+                Some(action) => {
+                    game.play(from, action);
+                    record.moves.push((from, action));
+                }
+                None => {
+                    let iccs = notation::to_iccs(from, Action::Go(to));
+                    return Err(RecordError::BadMove(record.moves.len() + 1, iccs));
+                }
+            }
+        }
+        Ok(record)
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+// drop {comments}, ;comments and (variations)
+fn strip_comments(movetext: &str) -> String {
+    let mut res = String::new();
+    let mut depth = 0;
+    let mut in_brace = false;
+    let mut in_line_comment = false;
+    for c in movetext.chars() {
+        if in_line_comment {
+            if c == '\n' {
+                in_line_comment = false;
+                res.push(' ');
+            }
+        } else if in_brace {
+            if c == '}' {
+                in_brace = false;
+                res.push(' ');
+            }
+        } else if c == '{' {
+            in_brace = true;
+        } else if c == ';' {
+            in_line_comment = true;
+        } else if c == '(' {
+            depth += 1;
+        } else if c == ')' {
+            // a stray one closes nothing
+            depth = (depth - 1).max(0);
+            res.push(' ');
+        } else if depth == 0 {
+            res.push(c);
+        }
+    }
+    res
+}
+
+const XQF_VERSION: u8 = 10;
+const XQF_HEADER_LEN: usize = 1024;
+const XQF_BOARD: usize = 16;
+const XQF_RESULT: usize = 51;
+const XQF_NONE: u8 = 0xff;
+const XQF_FROM_OFFSET: u8 = 24;
+const XQF_TO_OFFSET: u8 = 32;
+// the piece kept in each of the 16 slots of a side
+const XQF_SLOTS: [Piece; 16] = [
+    Piece::Che, Piece::Ma, Piece::Xiang, Piece::Shi, Piece::Jiang, Piece::Shi, Piece::Xiang, Piece::Ma, Piece::Che,
+    Piece::Pao, Piece::Pao,
+    Piece::Bing, Piece::Bing, Piece::Bing, Piece::Bing, Piece::Bing,
+];
+// (tag, offset of the length byte, max length)
+const XQF_TEXT_FIELDS: [(&str, usize, usize); 6] = [
+    ("Title", 80, 63),
+    ("Event", 208, 63),
+    ("Date", 272, 15),
+    ("Site", 288, 15),
+    ("Red", 304, 15),
+    ("Black", 320, 15),
+];
+
+// a length byte followed by the text, cut on a char boundary when too long.
+// XQStudio writes GBK, we write and read UTF-8, which only agree on ASCII.
+fn write_xqf_text(field: &mut [u8], text: &str) {
+    let mut len = text.len().min(field.len() - 1);
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    field[0] = len as u8;
+    field[1..len + 1].copy_from_slice(&text.as_bytes()[..len]);
+}
+
+fn read_xqf_text(field: &[u8]) -> String {
+    let len = (field[0] as usize).min(field.len() - 1);
+    String::from_utf8_lossy(&field[1..len + 1]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iccs(record: &GameRecord) -> Vec<String> {
+        record.moves.iter().map(|(from, action)| notation::to_iccs(*from, *action)).collect()
+    }
+
+    #[test]
+    fn comments_and_variations_are_skipped() {
+        let pgn = "[Event \"test\"]\n\n\
+                   1. H2-E2 {central cannon} H9-G7 ; the usual reply\n\
+                   2. H0-G2 (2. B0-C2 B9-C7 (2... C6-C5)) I9-H9 *\n";
+        let record = GameRecord::from_pgn(pgn).unwrap();
+        assert_eq!(iccs(&record), ["h2e2", "h9g7", "h0g2", "i9h9"]);
+        assert_eq!(record.tag("Event"), Some("test"));
+    }
+
+    #[test]
+    fn a_stray_parenthesis_closes_nothing() {
+        let record = GameRecord::from_pgn("1. H2-E2 ) H9-G7 2. H0-G2").unwrap();
+        assert_eq!(iccs(&record), ["h2e2", "h9g7", "h0g2"]);
+    }
+
+    #[test]
+    fn only_the_first_game_is_read() {
+        let pgn = "[Event \"first\"]\n\n1. H2-E2 H9-G7 *\n\n[Event \"second\"]\n\n1. B0-C2 *\n";
+        let record = GameRecord::from_pgn(pgn).unwrap();
+        assert_eq!(iccs(&record), ["h2e2", "h9g7"]);
+        assert_eq!(record.tags, [("Event".to_string(), "first".to_string())]);
+    }
+
+    #[test]
+    fn wxf_and_chinese_move_text() {
+        let wxf = "[Format \"WXF\"]\n\n1. C2=5 H8+7 2. H2+3 R9=8 *\n";
+        let chinese = "[Format \"Chinese\"]\n\n1. 炮二平五 马８进７ 2. 马二进三 车９平８ *\n";
+        let expected = ["h2e2", "h9g7", "h0g2", "i9h9"];
+        assert_eq!(iccs(&GameRecord::from_pgn(wxf).unwrap()), expected);
+        assert_eq!(iccs(&GameRecord::from_pgn(chinese).unwrap()), expected);
+    }
+
+    #[test]
+    fn fen_start_position() {
+        let fen = "3k5/9/9/9/9/9/R8/9/9/R3K4 w - - 0 1";
+        let pgn = format!("[FEN \"{}\"]\n\n1. A3-A4 D9-D8 *\n", fen);
+        let record = GameRecord::from_pgn(&pgn).unwrap();
+        assert_eq!(record.fen, fen);
+        assert_eq!(iccs(&record), ["a3a4", "d9d8"]);
+        let game = record.to_game().unwrap();
+        assert_eq!(game.to_fen(), "9/3k5/9/9/9/R8/9/9/9/R3K4 w - - 2 2");
+        // written back only because it is not the opening position
+        assert!(record.to_pgn().contains(&format!("[FEN \"{}\"]", fen)));
+        assert!(!GameRecord::from_pgn("1. H2-E2 *").unwrap().to_pgn().contains("[FEN"));
+    }
+
+    #[test]
+    fn bad_pgn_moves_are_reported() {
+        assert!(matches!(
+            GameRecord::from_pgn("1. H2-E2 H9-G7 2. H2-E2 *"),
+            Err(RecordError::BadMove(3, ref mv)) if mv == "H2-E2"
+        ));
+    }
+
+    fn xqf_of(moves: &str) -> Vec<u8> {
+        GameRecord::from_pgn(moves).unwrap().to_xqf()
+    }
+
+    #[test]
+    fn xqf_round_trip() {
+        let record = GameRecord::from_xqf(&xqf_of("[Result \"1-0\"]\n\n1. H2-E2 H9-G7 2. H0-G2 1-0")).unwrap();
+        assert_eq!(iccs(&record), ["h2e2", "h9g7", "h0g2"]);
+        assert_eq!(record.fen, format!("{} w - - 0 1", Board::from_fen(START_FEN).unwrap().to_fen()));
+        assert_eq!(record.tag("Result"), Some("1-0"));
+    }
+
+    #[test]
+    fn broken_xqf_is_refused() {
+        let data = xqf_of("1. H2-E2 H9-G7 *");
+        assert!(matches!(GameRecord::from_xqf(b"PGN"), Err(RecordError::NotXqf)));
+        assert!(matches!(
+            GameRecord::from_xqf(&data[..XQF_HEADER_LEN + 12]),
+            Err(RecordError::Truncated)
+        ));
+        let mut encrypted = data.clone();
+        encrypted[2] = 18;
+        assert!(matches!(GameRecord::from_xqf(&encrypted), Err(RecordError::EncryptedXqf(18))));
+        // the first move turned into a che jumping over its own bing
+        let mut illegal = data;
+        illegal[XQF_HEADER_LEN + 8] = XQF_FROM_OFFSET;
+        illegal[XQF_HEADER_LEN + 9] = 5 + XQF_TO_OFFSET;
+        assert!(matches!(
+            GameRecord::from_xqf(&illegal),
+            Err(RecordError::BadMove(1, ref mv)) if mv == "a0a5"
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+// Repetition rules. When a position comes up for the third time with the same side to move,
+// the moves played since its first occurrence are judged: a side all of whose moves there were
+// forbidden loses, when neither or both sides kept to forbidden moves the game is drawn.
+//
+// Forbidden under both rulesets are perpetual check and perpetual chase, also mixed. A move chases
+// when the moved piece newly threatens to take an enemy piece that is unprotected, or a che with
+// anything but a che. The jiang and bing may chase freely, and a bing that has not crossed the
+// river may be chased freely.
+//
+// The rulesets differ in perpetual mate threats (长杀), moves threatening mate on the next move:
+// the Chinese Xiangqi Association rules forbid them like checks, the Asian Xiangqi Federation
+// rules allow them, so there such a repetition is a draw. The finer cases of both rulebooks
+// (pinned protectors, chases by several pieces at once, ...) are not modelled.
+use crate::{
+    chess::{Board, ChessGame, DrawReason, Outcome, WinReason},
+    pieces::{Action, Piece, PlayerColor},
+    pos::Pos,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Ruleset {
+    #[default]
+    Asian,
+    Chinese,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Check,
+    Chase,
+    MateThreat,
+    Idle,
+}
+
+// pieces the one on `from` could take with a legal move
+fn targets(board: &Board, from: Pos) -> Vec<Pos> {
+    let Some(Some((color, piece))) = board.get(from) else { return vec![] };
+    let takes = piece
+        .moves(board, from, *color)
+        .into_iter()
+        .filter(|action| matches!(action, Action::Take(_)))
+        .collect();
+    board.filter_save_moves(*color, from, takes).into_iter().map(|action| action.target()).collect()
+}
+
+fn crossed_river(pos: Pos, color: PlayerColor) -> bool {
+    match color {
+        PlayerColor::Red => pos.1 >= 5,
+        PlayerColor::Black => pos.1 <= 4,
+    }
+}
+
+fn is_chase(before: &Board, after: &Board, from: Pos, to: Pos) -> bool {
+    let Some((color, attacker)) = *after.get(to).unwrap() else { return false };
+    if matches!(attacker, Piece::Jiang | Piece::Bing) {
+        return false;
+    }
+    let old = targets(before, from);
+    targets(after, to).into_iter().filter(|victim| !old.contains(victim)).any(|victim| {
+        let (victim_color, piece) = after.get(victim).unwrap().unwrap();
+        match piece {
+            Piece::Jiang => false,
+            Piece::Bing if !crossed_river(victim, victim_color) => false,
+            Piece::Che if attacker != Piece::Che => true,
+            _ => !after.play(to, Action::Take(victim)).is_attacked(victim, color.next()),
+        }
+    })
+}
+
+// whether `color`, if it could move again, would have a move leaving the other side without any
+fn threatens_mate(board: &Board, color: PlayerColor) -> bool {
+    board
+        .legal_moves(color)
+        .into_iter()
+        .any(|(from, action)| board.play(from, action).legal_moves(color.next()).is_empty())
+}
+
+fn classify(before: &Board, after: &Board, from: Pos, to: Pos, color: PlayerColor, ruleset: Ruleset) -> Kind {
+    if after.in_check(color.next()) {
+        Kind::Check
+    } else if is_chase(before, after, from, to) {
+        Kind::Chase
+    } else if ruleset == Ruleset::Chinese && threatens_mate(after, color) {
+        Kind::MateThreat
+    } else {
+        Kind::Idle
+    }
+}
+
+// what a side is to blame for, given the kinds of all its moves in the repetition
+fn violation(kinds: &[Kind]) -> Option<WinReason> {
+    if kinds.is_empty() || kinds.contains(&Kind::Idle) {
+        None
+    } else if kinds.iter().all(|kind| *kind == Kind::Check) {
+        Some(WinReason::PerpetualCheck)
+    } else if kinds.contains(&Kind::Chase) {
+        Some(WinReason::PerpetualChase)
+    } else {
+        Some(WinReason::PerpetualMateThreat)
+    }
+}
+
+impl ChessGame {
+    // None unless the current position has come up three times
+    pub(crate) fn repetition(&self) -> Option<Outcome> {
+        // positions before a capture can not come back, so look no further
+        let mut board = self.board.clone();
+        let mut boards = vec![board.clone()];
+        for m in self.history.iter().rev() {
+            if m.captured.is_some() {
+                break;
+            }
+            board.unmake(m.from, m.action, m.captured);
+            boards.push(board.clone());
+        }
+        boards.reverse();
+        let hash = self.board.hash();
+        let seen: Vec<usize> = (0..boards.len()).filter(|i| boards[*i].hash() == hash).collect();
+        if seen.len() < 3 {
+            return None;
+        }
+        let first = seen[seen.len() - 3];
+        let moves = &self.history[self.history.len() - (boards.len() - 1)..];
+        let mut kinds = [vec![], vec![]];
+        for i in first..boards.len() - 1 {
+            let m = moves[i];
+            let (color, _) = boards[i].get(m.from).unwrap().unwrap();
+            let kind = classify(&boards[i], &boards[i + 1], m.from, m.action.target(), color, self.ruleset);
+            kinds[if color == PlayerColor::Red { 0 } else { 1 }].push(kind);
+        }
+        match (violation(&kinds[0]), violation(&kinds[1])) {
+            (Some(reason), None) => Some(Outcome::Win(PlayerColor::Black, reason)),
+            (None, Some(reason)) => Some(Outcome::Win(PlayerColor::Red, reason)),
+            _ => Some(Outcome::Draw(DrawReason::Repetition)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    #[test]
+    fn mate_threats_count_under_chinese_rules_only() {
+        // the che on a8 holds rank 8, whichever che reaches rank 9 mates
+        let before = Board::from_fen("3k5/R8/9/9/1P7/9/8p/1R7/9/4K4 w").unwrap();
+        let (from, action) = notation::from_iccs(&before, "b2c2").unwrap();
+        let after = before.play(from, action);
+        let to = action.target();
+        assert_eq!(classify(&before, &after, from, to, PlayerColor::Red, Ruleset::Chinese), Kind::MateThreat);
+        assert_eq!(classify(&before, &after, from, to, PlayerColor::Red, Ruleset::Asian), Kind::Idle);
+    }
+}
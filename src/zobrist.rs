@@ -0,0 +1,37 @@
+// Zobrist keys: a position hashes to the xor of the keys of its pieces, plus
+// SIDE_KEY when black is to move. The keys come from a fixed seed, so hashes
+// stay the same across runs and builds and may be stored, e.g. in an opening book.
+use crate::{attacks::{side, POINTS}, pieces::{Piece, PlayerColor}};
+
+// splitmix64
+const fn next(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn piece_keys() -> [[u64; POINTS]; 14] {
+    let mut keys = [[0; POINTS]; 14];
+    let mut state = 0x5851_f42d_4c95_7f2d;
+    let mut piece = 0;
+    while piece < 14 {
+        let mut point = 0;
+        while point < POINTS {
+            let (s, key) = next(state);
+            state = s;
+            keys[piece][point] = key;
+            point += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+const PIECE_KEYS: [[u64; POINTS]; 14] = piece_keys();
+pub(crate) const SIDE_KEY: u64 = next(0x2545_f491_4f6c_dd1d).1;
+
+pub(crate) fn piece_key(color: PlayerColor, piece: Piece, point: usize) -> u64 {
+    PIECE_KEYS[side(color) * 7 + piece as usize][point]
+}
@@ -0,0 +1,7 @@
+use chinese_chess::{chess::ChessGame, notation};
+
+// plays a move given in ICCS, it has to be legal
+pub fn play(game: &mut ChessGame, iccs: &str) {
+    let (from, action) = notation::from_iccs(&game.board, iccs).unwrap();
+    game.play(from, action);
+}
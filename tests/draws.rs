@@ -0,0 +1,44 @@
+use chinese_chess::chess::{ChessGame, DrawReason, Outcome};
+
+mod common;
+use common::play;
+
+#[test]
+fn no_attacking_material_is_a_draw() {
+    let game = ChessGame::from_fen("3ak4/4a4/9/9/9/9/9/4B4/9/3AK4 w - - 0 1").unwrap();
+    assert_eq!(game.outcome(), Some(Outcome::Draw(DrawReason::InsufficientMaterial)));
+    let game = ChessGame::from_fen("3ak4/4a4/9/9/9/9/9/4B4/4p4/3AK4 w - - 0 1").unwrap();
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn sixty_quiet_moves_draw() {
+    let fen = "3k5/9/9/9/r8/9/9/9/9/4K3R w - - 119 80";
+    let mut game = ChessGame::from_fen(fen).unwrap();
+    assert_eq!(game.to_fen(), fen);
+    assert_eq!(game.outcome(), None);
+    play(&mut game, "i0i1");
+    assert_eq!(game.quiet_plies, 120);
+    assert_eq!(game.outcome(), Some(Outcome::Draw(DrawReason::MoveLimit)));
+    game.regret();
+    assert_eq!(game.quiet_plies, 119);
+    assert_eq!(game.outcome(), None);
+    game.move_limit = None;
+    play(&mut game, "i0i1");
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn captures_and_bing_advances_reset_the_count() {
+    let mut game = ChessGame::from_fen("3k5/9/9/9/r8/9/4p4/9/9/4K3R w - - 30 40").unwrap();
+    play(&mut game, "i0i4");
+    assert_eq!(game.quiet_plies, 31);
+    play(&mut game, "e3e2");
+    assert_eq!(game.quiet_plies, 0);
+    play(&mut game, "i4i5");
+    // going sideways the bing can come back
+    play(&mut game, "e2d2");
+    assert_eq!(game.quiet_plies, 2);
+    play(&mut game, "i5a5");
+    assert_eq!(game.quiet_plies, 0);
+}
@@ -0,0 +1,60 @@
+use chinese_chess::{
+    chess::ChessGame,
+    external::{EngineError, ExternalEngine},
+    notation,
+    pieces::Action,
+    pos::Pos,
+};
+use std::{thread, time::{Duration, Instant}};
+
+mod common;
+use common::play;
+
+fn wait(engine: &mut ExternalEngine, game: &ChessGame) -> Result<(Pos, Action), EngineError> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(reply) = engine.poll(game) {
+            return reply;
+        }
+        assert!(Instant::now() < deadline, "engine did not answer");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn plays_the_ucci_binary() {
+    let mut engine = ExternalEngine::launch(env!("CARGO_BIN_EXE_ucci"), "ucci").unwrap();
+    let mut game = ChessGame::new();
+    play(&mut game, "h2e2");
+    engine.go(&game, "go depth 1").unwrap();
+    let (from, action) = wait(&mut engine, &game).unwrap();
+    assert!(game.playable_move(from, action.target()).is_some());
+}
+
+#[test]
+fn stub_engine_replies() {
+    let stub = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/stub-engine.sh");
+    let mut engine = ExternalEngine::launch(stub, "ucci").unwrap();
+    let mut game = ChessGame::new();
+    play(&mut game, "h2e2");
+    engine.go(&game, "go depth 1").unwrap();
+    let (from, action) = wait(&mut engine, &game).unwrap();
+    assert_eq!(notation::to_iccs(from, action), "h9g7");
+    game.play(from, action);
+    // the stub answers g7h9 next, which red's turn does not allow
+    engine.go(&game, "go depth 1").unwrap();
+    assert!(matches!(wait(&mut engine, &game), Err(EngineError::IllegalMove(_))));
+}
+
+#[test]
+fn cancelled_move_is_ignored() {
+    let stub = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/stub-engine.sh");
+    let mut engine = ExternalEngine::launch(stub, "ucci").unwrap();
+    let mut game = ChessGame::new();
+    play(&mut game, "h2e2");
+    engine.go(&game, "go depth 1").unwrap();
+    engine.cancel();
+    // the stub's first answer, h9g7, belongs to the cancelled request
+    engine.go(&game, "go depth 1").unwrap();
+    assert!(matches!(wait(&mut engine, &game), Err(EngineError::IllegalMove(mv)) if mv == "g7h9"));
+}
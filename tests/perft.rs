@@ -0,0 +1,50 @@
+// Node counts published for xiangqi move generators, from the start position
+// and the positions other engines use for their perft suites.
+use chinese_chess::chess::{perft, ChessGame, START_FEN};
+
+fn perft_fen(fen: &str, depth: u32) -> u64 {
+    let game = ChessGame::from_fen(fen).unwrap();
+    perft(&game.board, game.player.unwrap(), depth)
+}
+
+const MIDDLE_GAME: &str = "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1";
+const KNIGHTS_AND_CANNONS: &str = "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1";
+const ENDGAME: &str = "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A3/5K3/2BC2B2 w - - 0 1";
+
+#[test]
+fn perft_start_position() {
+    assert_eq!(perft_fen(START_FEN, 1), 44);
+    assert_eq!(perft_fen(START_FEN, 2), 1920);
+    assert_eq!(perft_fen(START_FEN, 3), 79666);
+}
+
+#[test]
+fn perft_middle_game() {
+    assert_eq!(perft_fen(MIDDLE_GAME, 1), 38);
+    assert_eq!(perft_fen(MIDDLE_GAME, 2), 1128);
+    assert_eq!(perft_fen(MIDDLE_GAME, 3), 43929);
+}
+
+#[test]
+fn perft_knights_and_cannons() {
+    assert_eq!(perft_fen(KNIGHTS_AND_CANNONS, 1), 7);
+    assert_eq!(perft_fen(KNIGHTS_AND_CANNONS, 2), 281);
+    assert_eq!(perft_fen(KNIGHTS_AND_CANNONS, 3), 8620);
+}
+
+#[test]
+fn perft_endgame() {
+    assert_eq!(perft_fen(ENDGAME, 1), 25);
+    assert_eq!(perft_fen(ENDGAME, 2), 424);
+    assert_eq!(perft_fen(ENDGAME, 3), 9850);
+}
+
+// slow in debug builds: cargo test --release -- --ignored
+#[test]
+#[ignore]
+fn perft_depth_4() {
+    assert_eq!(perft_fen(START_FEN, 4), 3290240);
+    assert_eq!(perft_fen(MIDDLE_GAME, 4), 1339047);
+    assert_eq!(perft_fen(KNIGHTS_AND_CANNONS, 4), 326201);
+    assert_eq!(perft_fen(ENDGAME, 4), 202884);
+}
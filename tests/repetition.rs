@@ -0,0 +1,61 @@
+use chinese_chess::{
+    chess::{ChessGame, DrawReason, Outcome, WinReason, START_FEN},
+    notation,
+    pieces::PlayerColor,
+    repetition::Ruleset,
+};
+
+// plays the moves, the game must still be going on before the last one
+fn play(fen: &str, ruleset: Ruleset, moves: &[&str]) -> Option<Outcome> {
+    let mut game = ChessGame::from_fen(fen).unwrap();
+    game.ruleset = ruleset;
+    for iccs in moves {
+        assert_eq!(game.outcome(), None, "game over before {}", iccs);
+        let (from, action) = notation::from_iccs(&game.board, iccs).unwrap();
+        game.play(from, action);
+    }
+    game.outcome()
+}
+
+#[test]
+fn shuffling_is_a_draw() {
+    let moves = [
+        "h0g2", "h9g7", "g2h0", "g7h9", "h0g2", "h9g7", "g2h0", "g7h9",
+    ];
+    for ruleset in [Ruleset::Asian, Ruleset::Chinese] {
+        assert_eq!(play(START_FEN, ruleset, &moves[..7]), None);
+        assert_eq!(
+            play(START_FEN, ruleset, &moves),
+            Some(Outcome::Draw(DrawReason::Repetition))
+        );
+    }
+}
+
+#[test]
+fn perpetual_check_loses() {
+    let fen = "3k5/9/9/9/9/9/9/9/R8/4K4 w";
+    let moves = [
+        "a1a9", "d9d8", "a9a8", "d8d9", "a8a9", "d9d8", "a9a8", "d8d9", "a8a9",
+    ];
+    for ruleset in [Ruleset::Asian, Ruleset::Chinese] {
+        assert_eq!(
+            play(fen, ruleset, &moves),
+            Some(Outcome::Win(PlayerColor::Black, WinReason::PerpetualCheck))
+        );
+    }
+}
+
+#[test]
+fn perpetual_chase_loses() {
+    // the red che follows the unprotected black pao from file to file
+    let fen = "5k3/9/1c7/9/9/9/1R7/9/9/3K5 b";
+    let moves = [
+        "b7h7", "b3h3", "h7b7", "h3b3", "b7h7", "b3h3", "h7b7", "h3b3",
+    ];
+    for ruleset in [Ruleset::Asian, Ruleset::Chinese] {
+        assert_eq!(
+            play(fen, ruleset, &moves),
+            Some(Outcome::Win(PlayerColor::Black, WinReason::PerpetualChase))
+        );
+    }
+}
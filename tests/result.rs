@@ -0,0 +1,75 @@
+use chinese_chess::{
+    chess::{ChessGame, DrawReason, Outcome, WinReason},
+    notation,
+    pieces::PlayerColor,
+    record::GameRecord,
+};
+
+mod common;
+use common::play;
+
+#[test]
+fn resignation_ends_the_game() {
+    let mut game = ChessGame::new();
+    play(&mut game, "h2e2");
+    game.resign(PlayerColor::Black);
+    assert_eq!(game.outcome(), Some(Outcome::Win(PlayerColor::Red, WinReason::Resignation)));
+    assert!(game.playable_moves(notation::from_iccs(&game.board, "h9g7").unwrap().0).is_none());
+    // taking back the last move takes back the resignation
+    game.regret();
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn draw_offers() {
+    let mut game = ChessGame::new();
+    assert!(!game.accept_draw(PlayerColor::Black));
+    game.offer_draw(PlayerColor::Red);
+    game.decline_draw();
+    assert!(!game.accept_draw(PlayerColor::Black));
+    // nobody accepts their own offer
+    game.offer_draw(PlayerColor::Red);
+    assert!(!game.accept_draw(PlayerColor::Red));
+    assert_eq!(game.draw_offer, Some(PlayerColor::Red));
+    game.decline_draw();
+    // red offers with its move, black declines by moving
+    play(&mut game, "h2e2");
+    game.offer_draw(PlayerColor::Red);
+    play(&mut game, "h9g7");
+    assert_eq!(game.draw_offer, None);
+    game.offer_draw(PlayerColor::Red);
+    assert!(game.accept_draw(PlayerColor::Black));
+    assert_eq!(game.outcome(), Some(Outcome::Draw(DrawReason::Agreement)));
+}
+
+#[test]
+fn result_goes_into_the_record() {
+    let mut game = ChessGame::new();
+    play(&mut game, "h2e2");
+    game.resign(PlayerColor::Black);
+    let record = GameRecord::from_pgn(&GameRecord::from_game(&game).to_pgn()).unwrap();
+    assert_eq!(record.tag("Result"), Some("1-0"));
+    assert_eq!(record.to_game().unwrap().outcome(), game.outcome());
+    let xqf = GameRecord::from_xqf(&GameRecord::from_game(&game).to_xqf()).unwrap();
+    assert_eq!(xqf.tag("Result"), Some("1-0"));
+
+    let mut game = ChessGame::new();
+    game.offer_draw(PlayerColor::Red);
+    game.accept_draw(PlayerColor::Black);
+    let record = GameRecord::from_pgn(&GameRecord::from_game(&game).to_pgn()).unwrap();
+    assert_eq!(record.tag("Result"), Some("1/2-1/2"));
+    assert_eq!(record.to_game().unwrap().outcome(), Some(Outcome::Draw(DrawReason::Agreement)));
+}
+
+#[test]
+fn black_opening_goes_into_the_record() {
+    let mut game = ChessGame::new();
+    play(&mut game, "h7e7");
+    play(&mut game, "h2e2");
+    assert!(game.start_fen.contains(" b "));
+    let loaded = GameRecord::from_pgn(&GameRecord::from_game(&game).to_pgn()).unwrap().to_game().unwrap();
+    assert_eq!(loaded.to_fen(), game.to_fen());
+    assert_eq!(loaded.board.hash(), game.board.hash());
+    let loaded = GameRecord::from_xqf(&GameRecord::from_game(&game).to_xqf()).unwrap().to_game().unwrap();
+    assert_eq!(loaded.to_fen(), game.to_fen());
+}
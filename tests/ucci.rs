@@ -0,0 +1,43 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn run(input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ucci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn handshake() {
+    let output = run("ucci\nisready\nquit\n");
+    assert!(output.contains("ucciok"));
+    assert!(output.contains("readyok"));
+    assert!(output.ends_with("bye\n"));
+}
+
+#[test]
+fn mate_in_one() {
+    // one che holds the second rank, the other checks along the back rank and the red
+    // jiang guards the e file. The black zu blocks the d file and keeps black from
+    // being stalemated by any other move.
+    let output = run("position fen 3k5/8R/9/9/9/9/3p5/9/9/R3K4 w - - 0 1\ngo depth 2\n");
+    assert!(output.contains("bestmove a0a9"), "{}", output);
+}
+
+#[test]
+fn bestmove_after_moves() {
+    let output = run("position startpos moves h2e2 h9g7\ngo depth 1\n");
+    assert!(output.lines().any(|line| line.starts_with("bestmove ")), "{}", output);
+}
+
+#[test]
+fn no_move_when_mated() {
+    let output = run("position fen R2k5/R8/9/9/9/9/9/9/9/4K4 b - - 0 1\ngo depth 1\n");
+    assert!(output.contains("nobestmove"), "{}", output);
+}